@@ -1,67 +1,321 @@
-﻿use std::fmt::Display;
-use crate::tools::{parse_numbers_i32, usize_to_i32};
-pub fn part_one(input: String) -> impl Display {
-    let instructions:Vec<(&str, &str)> = input
-        .lines()
-        .into_iter()
-        .map(|f| split_line(f))
-        .collect();
+﻿use std::collections::{HashMap, HashSet};
+use std::fmt;
+use std::fmt::Display;
+use std::io::BufRead;
+use crate::tools::parse_numbers_i64;
 
-    let mut left_list:Vec<i32> = Vec::new();
-    let mut right_list:Vec<i32> = Vec::new();
+// A malformed line fails both parts with the reconciler's message instead of panicking.
+pub fn parse(input: String) -> Result<LocationReconciler, String> {
+    LocationReconciler::from_reader(input.as_bytes())
+}
+
+pub fn part_one(reconciler: &Result<LocationReconciler, String>) -> Result<u64, String> {
+    reconciler.as_ref().map_err(|e| e.clone()).and_then(|reconciler| reconciler.distance())
+}
+
+pub fn part_two(reconciler: &Result<LocationReconciler, String>) -> Result<i64, String> {
+    reconciler.as_ref().map_err(|e| e.clone()).and_then(|reconciler| reconciler.similarity())
+}
+
+// How the first two lists differ, then the distance and similarity between every pair of lists.
+pub fn explain(reconciler: &LocationReconciler) -> String {
+    let diagnostics = reconciler.diagnostics(5).map(|diagnostics| diagnostics.to_string()).unwrap_or_else(|message| message);
+    let matrix = reconciler.comparison_matrix().map(|matrix| matrix.to_string()).unwrap_or_else(|message| message);
+    format!("{}\n\n{}", diagnostics, matrix)
+}
+
+// Each column of the input is one historian group's list. Counting ids per column means
+// similarity is a lookup per id instead of a scan of the other list.
+pub struct LocationReconciler {
+    columns: Vec<LocationIdRange>
+}
+
+impl LocationReconciler {
+    pub fn from_reader(reader: impl BufRead) -> Result<Self, String> {
+        let mut columns: Vec<LocationIdRange> = Vec::new();
+
+        for (index, line) in reader.lines().enumerate() {
+            let line = line.map_err(|e| format!("Could not read line {}: {}", index + 1, e))?;
+            if line.trim().is_empty() {
+                continue;
+            }
+
+            let entries: Vec<&str> = line.split_whitespace().collect();
+            if columns.is_empty() {
+                if entries.len() < 2 {
+                    return Err(format!("Line {} needs at least two location ids", index + 1));
+                }
+                columns = (0..entries.len()).map(|_| LocationIdRange::new()).collect();
+            }
+            if entries.len() != columns.len() {
+                return Err(format!("Line {} has {} location ids, expected {}", index + 1, entries.len(), columns.len()));
+            }
+
+            for (column, entry) in columns.iter_mut().zip(entries) {
+                let id = parse_location_id(entry).ok_or(format!("Line {} has an invalid location id {}", index + 1, entry))?;
+                column.push(id);
+            }
+        }
+
+        if columns.is_empty() {
+            return Err("no location ids".to_string());
+        }
+        columns.iter_mut().for_each(|column| column.values.sort_unstable());
+
+        Ok(LocationReconciler { columns })
+    }
+
+    pub fn column_count(&self) -> usize {
+        self.columns.len()
+    }
+
+    fn column(&self, index: usize) -> Result<&LocationIdRange, String> {
+        self.columns.get(index).ok_or(format!("no location list {}, there are {}", index, self.columns.len()))
+    }
+
+    pub fn distance(&self) -> Result<u64, String> {
+        self.distance_between(0, 1)
+    }
+
+    pub fn similarity(&self) -> Result<i64, String> {
+        self.similarity_between(0, 1)
+    }
 
-    instructions.iter().for_each(|(l, r)| {
-        left_list.push(parse_numbers_i32(l).unwrap().1);
-        right_list.push(parse_numbers_i32(r).unwrap().1);
-    });
+    pub fn distance_between(&self, left: usize, right: usize) -> Result<u64, String> {
+        let (left, right) = (self.column(left)?, self.column(right)?);
+        left.values.iter().zip(right.values.iter())
+            .try_fold(0u64, |total, (l, r)| total.checked_add(l.abs_diff(*r)).ok_or("distance overflows".to_string()))
+    }
+
+    pub fn similarity_between(&self, left: usize, right: usize) -> Result<i64, String> {
+        let (left, right) = (self.column(left)?, self.column(right)?);
+        left.frequency.iter().try_fold(0i64, |total, (id, left_count)| {
+            let score = left_count.checked_mul(right.count(*id))
+                .and_then(|count| i64::try_from(count).ok())
+                .and_then(|count| id.checked_mul(count))
+                .ok_or(format!("similarity score of location id {} overflows", id))?;
+            total.checked_add(score).ok_or("similarity overflows".to_string())
+        })
+    }
 
-    left_list.sort();
-    right_list.sort();
-    let mut total = 0;
-    for x in 0..left_list.len() {
-        let difference = left_list[x] - right_list[x];
-        total += difference.abs();
+    pub fn comparison_matrix(&self) -> Result<ComparisonMatrix, String> {
+        let indexes = 0..self.columns.len();
+        Ok(ComparisonMatrix {
+            distances: indexes.clone().map(|l| indexes.clone().map(|r| self.distance_between(l, r)).collect()).collect::<Result<_, _>>()?,
+            similarities: indexes.clone().map(|l| indexes.clone().map(|r| self.similarity_between(l, r)).collect()).collect::<Result<_, _>>()?
+        })
+    }
+
+    pub fn diagnostics(&self, duplicate_limit: usize) -> Result<Diagnostics, String> {
+        self.diagnostics_between(0, 1, duplicate_limit)
+    }
+
+    pub fn diagnostics_between(&self, left: usize, right: usize, duplicate_limit: usize) -> Result<Diagnostics, String> {
+        let (left, right) = (self.column(left)?, self.column(right)?);
+        let left_ids: HashSet<&i64> = left.frequency.keys().collect();
+        let right_ids: HashSet<&i64> = right.frequency.keys().collect();
+
+        let mut left_only: Vec<i64> = left_ids.difference(&right_ids).map(|id| **id).collect();
+        let mut right_only: Vec<i64> = right_ids.difference(&left_ids).map(|id| **id).collect();
+        left_only.sort_unstable();
+        right_only.sort_unstable();
+
+        Ok(Diagnostics {
+            left_only,
+            right_only,
+            left_duplicates: most_frequent(&left.frequency, duplicate_limit),
+            right_duplicates: most_frequent(&right.frequency, duplicate_limit)
+        })
     }
-    total
 }
 
-pub fn part_two(input: String) -> impl Display {
-    let instructions:Vec<(&str, &str)> = input
-        .lines()
-        .into_iter()
-        .map(|f| split_line(f))
-        .collect();
+// One column of location ids, kept sorted for the pairwise distance along with how often each id appears.
+struct LocationIdRange {
+    values: Vec<i64>,
+    frequency: HashMap<i64, u64>
+}
+
+impl LocationIdRange {
+    fn new() -> Self {
+        LocationIdRange { values: Vec::new(), frequency: HashMap::new() }
+    }
+
+    fn push(&mut self, id: i64) {
+        self.values.push(id);
+        *self.frequency.entry(id).or_insert(0) += 1;
+    }
+
+    fn count(&self, id: i64) -> u64 {
+        self.frequency.get(&id).copied().unwrap_or(0)
+    }
+}
 
-    let mut left_list:Vec<i32> = Vec::new();
-    let mut right_list:Vec<i32> = Vec::new();
+fn parse_location_id(input: &str) -> Option<i64> {
+    match parse_numbers_i64(input) {
+        Ok(("", id)) => Some(id),
+        _ => None
+    }
+}
 
-    instructions.iter().for_each(|(l, r)| {
-        left_list.push(parse_numbers_i32(l).unwrap().1);
-        right_list.push(parse_numbers_i32(r).unwrap().1);
-    });
+fn most_frequent(frequency: &HashMap<i64, u64>, limit: usize) -> Vec<(i64, u64)> {
+    let mut duplicates: Vec<(i64, u64)> = frequency.iter().filter(|(_, count)| **count > 1).map(|(id, count)| (*id, *count)).collect();
+    duplicates.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(&b.0)));
+    duplicates.truncate(limit);
+    duplicates
+}
 
-    left_list.sort();
-    right_list.sort();
+#[derive(Debug, PartialEq, Eq)]
+pub struct Diagnostics {
+    pub left_only: Vec<i64>,
+    pub right_only: Vec<i64>,
+    pub left_duplicates: Vec<(i64, u64)>,
+    pub right_duplicates: Vec<(i64, u64)>
+}
 
-    let mut total:i32 = 0;
+impl fmt::Display for Diagnostics {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "Only in left list: {:?}", self.left_only)?;
+        writeln!(f, "Only in right list: {:?}", self.right_only)?;
+        writeln!(f, "Most frequent in left list: {:?}", self.left_duplicates)?;
+        write!(f, "Most frequent in right list: {:?}", self.right_duplicates)
+    }
+}
 
-    left_list.iter().for_each(|v| {
-        let matches = right_list.clone().into_iter().filter(|x| x == v).count();
-        total += (v * usize_to_i32(matches).unwrap());
-    });
+pub struct ComparisonMatrix {
+    pub distances: Vec<Vec<u64>>,
+    pub similarities: Vec<Vec<i64>>
+}
 
-    total
+impl fmt::Display for ComparisonMatrix {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "Distance")?;
+        write_matrix(f, &self.distances)?;
+        writeln!(f, "Similarity")?;
+        write_matrix(f, &self.similarities)
+    }
 }
 
-fn split_line(input: &str) -> (&str, &str) {
-    let mut entries = input.split_whitespace();
+fn write_matrix<T: Display>(f: &mut fmt::Formatter, rows: &Vec<Vec<T>>) -> fmt::Result {
+    let cells: Vec<Vec<String>> = rows.iter().map(|row| row.iter().map(|v| v.to_string()).collect()).collect();
+    let width = cells.iter().flatten().map(|c| c.len()).max().unwrap_or(1).max(4);
 
-    let left = entries.next();
-    let right = entries.next();
+    write!(f, "{:>4}", "")?;
+    for column in 0..rows.len() {
+        write!(f, " {:>width$}", format!("#{}", column + 1), width = width)?;
+    }
+    writeln!(f)?;
+    for (index, row) in cells.iter().enumerate() {
+        write!(f, "{:>4}", format!("#{}", index + 1))?;
+        for cell in row {
+            write!(f, " {:>width$}", cell, width = width)?;
+        }
+        writeln!(f)?;
+    }
 
-    (left.unwrap(), right.unwrap())
+    Ok(())
 }
 
-struct LocationIdRange {
-    value: i32
-}
\ No newline at end of file
+#[cfg(test)]
+mod tests {
+    use super::{explain, parse, part_one, LocationReconciler};
+
+    const INPUT: &str = r#"3   4
+4   3
+2   5
+1   3
+3   9
+3   3"#;
+
+    #[test]
+    fn lists_can_be_reconciled() {
+        let reconciler = LocationReconciler::from_reader(INPUT.as_bytes()).unwrap();
+
+        assert_eq!(reconciler.distance(), Ok(11));
+        assert_eq!(reconciler.similarity(), Ok(31));
+    }
+
+    #[test]
+    fn large_ids_do_not_overflow() {
+        let input = "4000000000 1\n4000000000 4000000000\n1 4000000000";
+        let reconciler = LocationReconciler::from_reader(input.as_bytes()).unwrap();
+
+        assert_eq!(reconciler.distance(), Ok(0));
+        assert_eq!(reconciler.similarity(), Ok(16000000001));
+    }
+
+    #[test]
+    fn overflowing_totals_are_reported() {
+        let input = "9223372036854775807 -9223372036854775808\n9223372036854775807 -9223372036854775808";
+        let reconciler = LocationReconciler::from_reader(input.as_bytes()).unwrap();
+
+        assert_eq!(reconciler.distance(), Err("distance overflows".to_string()));
+
+        let input = "9223372036854775807 9223372036854775807\n1 9223372036854775807";
+        let reconciler = LocationReconciler::from_reader(input.as_bytes()).unwrap();
+
+        assert_eq!(reconciler.similarity(), Err("similarity score of location id 9223372036854775807 overflows".to_string()));
+    }
+
+    #[test]
+    fn empty_input_is_reported() {
+        assert_eq!(LocationReconciler::from_reader("".as_bytes()).err(), Some("no location ids".to_string()));
+        assert_eq!(LocationReconciler::from_reader("\n  \n".as_bytes()).err(), Some("no location ids".to_string()));
+    }
+
+    #[test]
+    fn diagnostics_report_unmatched_and_duplicate_ids() {
+        let reconciler = LocationReconciler::from_reader(INPUT.as_bytes()).unwrap();
+        let diagnostics = reconciler.diagnostics(1).unwrap();
+
+        assert_eq!(diagnostics.left_only, vec![1, 2]);
+        assert_eq!(diagnostics.right_only, vec![5, 9]);
+        assert_eq!(diagnostics.left_duplicates, vec![(3, 3)]);
+        assert_eq!(diagnostics.right_duplicates, vec![(3, 3)]);
+    }
+
+    #[test]
+    fn malformed_lines_are_reported() {
+        let result = LocationReconciler::from_reader("3 4\n4\n".as_bytes());
+
+        assert_eq!(result.err(), Some("Line 2 has 1 location ids, expected 2".to_string()));
+    }
+
+    #[test]
+    fn any_pair_of_columns_can_be_compared() {
+        let input = r#"3   4   3
+4   3   4
+2   5   2
+1   3   1
+3   9   3
+3   3   3"#;
+        let reconciler = LocationReconciler::from_reader(input.as_bytes()).unwrap();
+
+        assert_eq!(reconciler.column_count(), 3);
+        assert_eq!(reconciler.distance_between(0, 2), Ok(0));
+        assert_eq!(reconciler.similarity_between(2, 1), Ok(31));
+
+        assert_eq!(reconciler.distance_between(0, 3), Err("no location list 3, there are 3".to_string()));
+        assert_eq!(reconciler.similarity_between(5, 1), Err("no location list 5, there are 3".to_string()));
+        assert!(reconciler.diagnostics_between(1, 3, 5).is_err());
+
+        let matrix = reconciler.comparison_matrix().unwrap();
+        assert_eq!(matrix.distances[1][2], 11);
+        assert_eq!(matrix.distances[2][1], 11);
+        assert_eq!(matrix.similarities[0][0], 3 * 9 + 4 + 2 + 1);
+    }
+
+    #[test]
+    fn malformed_input_fails_the_parts() {
+        assert_eq!(part_one(&parse(INPUT.to_string())), Ok(11));
+        assert_eq!(part_one(&parse("3 4\n4 x".to_string())), Err("Line 2 has an invalid location id x".to_string()));
+    }
+
+    #[test]
+    fn explanation_shows_diagnostics_and_matrix() {
+        let explanation = explain(&LocationReconciler::from_reader(INPUT.as_bytes()).unwrap());
+
+        assert!(explanation.starts_with("Only in left list: [1, 2]"));
+        assert!(explanation.contains("Distance\n"));
+        assert!(explanation.contains("Similarity\n"));
+    }
+}
@@ -1,23 +1,150 @@
-﻿use std::fmt::Display;
+﻿use std::fmt;
+use std::fmt::Display;
 use crate::tools::{parse_numbers_i32, parse_numbers_i64};
 
-pub fn part_one(input: String) -> impl Display {
-    let valid = input.lines().map(|line| Report::parse(line))
-        .filter(|report| report.is_safe(&report.levels)).into_iter().count();
+pub fn parse(input: String) -> Vec<Report> {
+    input.lines().map(|line| Report::parse(line)).collect()
+}
+
+pub fn part_one(reports: &Vec<Report>) -> impl Display {
+    count_safe(reports, &SafetyRules::standard())
+}
+
+pub fn part_two(reports: &Vec<Report>) -> impl Display {
+    count_safe(reports, &SafetyRules::with_dampener())
+}
+
+pub fn explain(reports: &Vec<Report>) -> String {
+    let rules = SafetyRules::with_dampener();
+    let rows: Vec<[String; 4]> = reports.iter().enumerate().map(|(index, report)| {
+        let verdict = report.verdict(&rules);
+        let levels = report.levels.iter().map(|l| l.to_string()).collect::<Vec<String>>().join(" ");
+        let outcome = match &verdict.safety {
+            Safety::Safe => "safe".to_string(),
+            Safety::SafeWithout(removed) => format!("safe without index {}", removed.iter().map(|i| i.to_string()).collect::<Vec<String>>().join(", ")),
+            Safety::Unsafe => "unsafe".to_string()
+        };
+        let problem = verdict.violation.map(|v| v.to_string()).unwrap_or_default();
+        [(index + 1).to_string(), levels, outcome, problem]
+    }).collect();
+
+    let headers = ["Report", "Levels", "Verdict", "First problem"];
+    let widths: Vec<usize> = (0..headers.len())
+        .map(|c| rows.iter().map(|r| r[c].len()).chain([headers[c].len()]).max().unwrap())
+        .collect();
+
+    let mut table = String::new();
+    table.push_str(&format_row(&headers.map(|h| h.to_string()), &widths));
+    table.push_str(&format_row(&widths.iter().map(|w| "-".repeat(*w)).collect::<Vec<String>>(), &widths));
+    for row in &rows {
+        table.push_str(&format_row(row, &widths));
+    }
+    table.push_str(&format!("{} of {} reports safe, {} with the dampener", count_safe(reports, &SafetyRules::standard()), reports.len(), count_safe(reports, &rules)));
+
+    table
+}
+
+fn format_row(row: &[String], widths: &Vec<usize>) -> String {
+    let line = format!("{:>w0$} | {:<w1$} | {:<w2$} | {}", row[0], row[1], row[2], row[3], w0 = widths[0], w1 = widths[1], w2 = widths[2]);
+    format!("{}\n", line.trim_end())
+}
+
+pub fn count_safe(reports: &Vec<Report>, rules: &SafetyRules) -> usize {
+    reports.iter().filter(|report| report.evaluate(rules) != Safety::Unsafe).count()
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Direction {
+    Increasing,
+    Decreasing,
+    Either
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SafetyRules {
+    min_step: i32,
+    max_step: i32,
+    direction: Direction,
+    removable_levels: usize
+}
+
+impl SafetyRules {
+    pub fn standard() -> Self {
+        SafetyRules { min_step: 1, max_step: 3, direction: Direction::Either, removable_levels: 0 }
+    }
+
+    pub fn with_dampener() -> Self {
+        SafetyRules { removable_levels: 1, ..SafetyRules::standard() }
+    }
 
+    pub fn new(min_step: i32, max_step: i32, direction: Direction, removable_levels: usize) -> Result<Self, String> {
+        if min_step > max_step {
+            return Err(format!("minimum step {} is larger than maximum step {}", min_step, max_step));
+        }
+
+        Ok(SafetyRules { min_step, max_step, direction, removable_levels })
+    }
+
+    fn directions(&self) -> Vec<Direction> {
+        match self.direction {
+            Direction::Either => vec![Direction::Increasing, Direction::Decreasing],
+            direction => vec![direction]
+        }
+    }
 
-    valid
+    fn step_allowed(&self, from: i32, to: i32, direction: Direction) -> bool {
+        let step = match direction {
+            Direction::Decreasing => from - to,
+            _ => to - from
+        };
+        step >= self.min_step && step <= self.max_step
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Reason {
+    DirectionChange,
+    ZeroStep,
+    JumpTooSmall,
+    JumpTooLarge
 }
 
-pub fn part_two(input: String) -> impl Display {
-    let valid = input.lines().map(|line| Report::parse(line))
-        .filter(|report| report.is_safe_with_problem_dampener()).into_iter().count();
+// The first pair of adjacent levels that breaks the rules, by index of the first level.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Violation {
+    index: usize,
+    from: i32,
+    to: i32,
+    reason: Reason
+}
 
+impl fmt::Display for Violation {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let reason = match self.reason {
+            Reason::DirectionChange => "changes direction",
+            Reason::ZeroStep => "does not change",
+            Reason::JumpTooSmall => "step too small",
+            Reason::JumpTooLarge => "jump too large"
+        };
+        write!(f, "{} -> {} at index {} {}", self.from, self.to, self.index, reason)
+    }
+}
 
-    valid
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Verdict {
+    safety: Safety,
+    violation: Option<Violation>
 }
 
-struct Report{
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Safety {
+    Safe,
+    // Safe once the levels at these indexes are removed
+    SafeWithout(Vec<usize>),
+    Unsafe
+}
+
+pub struct Report{
     levels: Vec<i32>
 }
 
@@ -28,49 +155,155 @@ impl Report {
         Report { levels: numbers }
     }
 
-    fn is_safe(&self, levels: &Vec<i32>) -> bool {
-        let mut windows = levels.windows(2);
+    pub fn verdict(&self, rules: &SafetyRules) -> Verdict {
+        Verdict { safety: self.evaluate(rules), violation: self.first_violation(rules) }
+    }
 
-        let mut differences:Vec<i32> = windows.into_iter()
-            .map(|x| x[1] - x[0]).collect();
+    // With either direction allowed, the first level that moves decides which way the report should go.
+    fn first_violation(&self, rules: &SafetyRules) -> Option<Violation> {
+        let mut direction = rules.direction;
+        for (index, pair) in self.levels.windows(2).enumerate() {
+            let step = pair[1] - pair[0];
+            if direction == Direction::Either && step != 0 {
+                direction = if step > 0 { Direction::Increasing } else { Direction::Decreasing };
+            }
 
+            let wrong_way = (direction == Direction::Increasing && step < 0) || (direction == Direction::Decreasing && step > 0);
+            let reason = if step.abs() < rules.min_step && step == 0 {
+                Some(Reason::ZeroStep)
+            } else if wrong_way {
+                Some(Reason::DirectionChange)
+            } else if step.abs() < rules.min_step {
+                Some(Reason::JumpTooSmall)
+            } else if step.abs() > rules.max_step {
+                Some(Reason::JumpTooLarge)
+            } else {
+                None
+            };
 
-        let all_changes = differences.iter().all(|f| *f > 0) || differences.iter().all(|f| *f < 0);
-        let no_large_jumps = differences.iter().all(|f| f.abs() < 4);
+            if let Some(reason) = reason {
+                return Some(Violation { index, from: pair[0], to: pair[1], reason });
+            }
+        }
 
-        all_changes && no_large_jumps
+        None
     }
 
-    fn is_safe_with_problem_dampener(&self) -> bool {
-        let initial = self.is_safe(&self.levels);
+    pub fn evaluate(&self, rules: &SafetyRules) -> Safety {
+        let mut best: Option<Vec<usize>> = None;
+        for direction in rules.directions() {
+            let removed = match rules.removable_levels {
+                0 => self.chain_holds(rules, direction).then(Vec::new),
+                1 => self.single_removal(rules, direction),
+                k => self.fewest_removals(rules, direction, k)
+            };
+            if let Some(removed) = removed {
+                if best.as_ref().map_or(true, |b| removed.len() < b.len()) {
+                    best = Some(removed);
+                }
+            }
+        }
 
-        if initial {
-            return initial;
+        match best {
+            None => Safety::Unsafe,
+            Some(removed) if removed.is_empty() => Safety::Safe,
+            Some(removed) => Safety::SafeWithout(removed)
         }
+    }
+
+    fn chain_holds(&self, rules: &SafetyRules, direction: Direction) -> bool {
+        self.levels.windows(2).all(|w| rules.step_allowed(w[0], w[1], direction))
+    }
 
-        for index in 0..self.levels.len() {
-            let mut vec = self.levels.clone();
-            vec.remove(index);
-            let damp_check = self.is_safe(&vec);
-            if(damp_check){
-                return damp_check;
+    // Linear check for one removable level: a level can go if everything before it and everything
+    // after it is already valid and its two neighbours are allowed to sit next to each other.
+    fn single_removal(&self, rules: &SafetyRules, direction: Direction) -> Option<Vec<usize>> {
+        let levels = &self.levels;
+        let n = levels.len();
+        if n < 2 || self.chain_holds(rules, direction) {
+            return Some(Vec::new());
+        }
+
+        let mut prefix_valid = vec![true; n];
+        for i in 1..n {
+            prefix_valid[i] = prefix_valid[i - 1] && rules.step_allowed(levels[i - 1], levels[i], direction);
+        }
+        let mut suffix_valid = vec![true; n];
+        for i in (0..n - 1).rev() {
+            suffix_valid[i] = suffix_valid[i + 1] && rules.step_allowed(levels[i], levels[i + 1], direction);
+        }
+
+        (0..n).find(|&i| {
+            let before = i == 0 || prefix_valid[i - 1];
+            let after = i == n - 1 || suffix_valid[i + 1];
+            let bridge = i == 0 || i == n - 1 || rules.step_allowed(levels[i - 1], levels[i + 1], direction);
+            before && after && bridge
+        }).map(|i| vec![i])
+    }
+
+    // General case: removals[i][j] holds the cheapest removed indexes for a valid chain ending at level i
+    // that skipped j levels in total. Only the last k + 1 levels can be the previous kept level.
+    fn fewest_removals(&self, rules: &SafetyRules, direction: Direction, k: usize) -> Option<Vec<usize>> {
+        let levels = &self.levels;
+        let n = levels.len();
+        if n == 0 {
+            return Some(Vec::new());
+        }
+
+        let mut removals: Vec<Vec<Option<Vec<usize>>>> = vec![vec![None; k + 1]; n];
+        for i in 0..=k.min(n - 1) {
+            removals[i][i] = Some((0..i).collect());
+        }
+        for i in 1..n {
+            for previous in i.saturating_sub(k + 1)..i {
+                if !rules.step_allowed(levels[previous], levels[i], direction) {
+                    continue;
+                }
+                let skipped = i - previous - 1;
+                for j in 0..=k - skipped {
+                    let Some(removed) = &removals[previous][j] else {
+                        continue;
+                    };
+                    if removals[i][j + skipped].is_none() {
+                        let mut removed = removed.clone();
+                        removed.extend(previous + 1..i);
+                        removals[i][j + skipped] = Some(removed);
+                    }
+                }
             }
         }
 
-        false
+        (0..=k.min(n - 1)).filter_map(|removed_at_end| {
+            let last = n - 1 - removed_at_end;
+            (0..=k - removed_at_end).find_map(|j| removals[last][j].clone()).map(|mut removed| {
+                removed.extend(last + 1..n);
+                removed
+            })
+        }).min_by_key(|removed| removed.len())
     }
 
 }
 #[cfg(test)]
 mod tests {
-    use super::{Report};
+    use crate::property;
+    use super::{count_safe, explain, parse, Direction, Reason, Report, Safety, SafetyRules, Violation};
+
+    // The original dampener: try the report again without each level in turn.
+    fn dampener_by_removing_each_level(report: &Report, rules: &SafetyRules) -> bool {
+        let strict = SafetyRules { removable_levels: 0, ..*rules };
+        (0..report.levels.len()).any(|index| {
+            let mut levels = report.levels.clone();
+            levels.remove(index);
+            Report { levels }.evaluate(&strict) == Safety::Safe
+        }) || report.evaluate(&strict) == Safety::Safe
+    }
 
     #[test]
     fn reports_can_be_found_safe() {
         let input = r#"7 6 4 2 1"#;
         let report = Report::parse(input);
 
-        assert_eq!(report.is_safe(&report.levels), true);
+        assert_eq!(report.evaluate(&SafetyRules::standard()), Safety::Safe);
     }
 
     #[test]
@@ -78,7 +311,7 @@ mod tests {
         let input = r#"1 2 7 8 9"#;
         let report = Report::parse(input);
 
-        assert_eq!(report.is_safe(&report.levels), false);
+        assert_eq!(report.evaluate(&SafetyRules::standard()), Safety::Unsafe);
     }
 
     #[test]
@@ -86,7 +319,7 @@ mod tests {
         let input = r#"1 3 2 4 5"#;
         let report = Report::parse(input);
 
-        assert_eq!(report.is_safe(&report.levels), false);
+        assert_eq!(report.evaluate(&SafetyRules::standard()), Safety::Unsafe);
     }
 
     #[test]
@@ -94,7 +327,7 @@ mod tests {
         let input = r#"1 3 2 4 5"#;
         let report = Report::parse(input);
 
-        assert_eq!(report.is_safe_with_problem_dampener(), true);
+        assert_ne!(report.evaluate(&SafetyRules::with_dampener()), Safety::Unsafe);
     }
 
     #[test]
@@ -102,6 +335,94 @@ mod tests {
         let input = r#"9 7 6 2 1"#;
         let report = Report::parse(input);
 
-        assert_eq!(report.is_safe_with_problem_dampener(), false);
+        assert_eq!(report.evaluate(&SafetyRules::with_dampener()), Safety::Unsafe);
+    }
+
+    #[test]
+    fn dampener_reports_the_level_to_remove() {
+        let report = Report::parse("1 3 2 4 5");
+
+        assert_eq!(report.evaluate(&SafetyRules::with_dampener()), Safety::SafeWithout(vec![1]));
+        assert_eq!(Report::parse("8 6 4 4 1").evaluate(&SafetyRules::with_dampener()), Safety::SafeWithout(vec![2]));
+        assert_eq!(Report::parse("1 2 7 8 9").evaluate(&SafetyRules::with_dampener()), Safety::Unsafe);
+    }
+
+    #[test]
+    fn rules_can_be_configured() {
+        let report = Report::parse("1 5 9 13");
+        let wide_steps = SafetyRules::new(4, 4, Direction::Increasing, 0).unwrap();
+
+        assert_eq!(report.evaluate(&wide_steps), Safety::Safe);
+        assert_eq!(report.evaluate(&SafetyRules { direction: Direction::Decreasing, ..wide_steps }), Safety::Unsafe);
+    }
+
+    #[test]
+    fn rules_reject_a_minimum_step_above_the_maximum() {
+        assert!(SafetyRules::new(3, 1, Direction::Either, 0).is_err());
+    }
+
+    #[test]
+    fn parsed_reports_can_be_counted_with_custom_rules() {
+        let reports = parse("1 5 9 13\n1 2 3 4\n1 5 9 20".to_string());
+        let rules = SafetyRules::new(4, 4, Direction::Either, 1).unwrap();
+
+        assert_eq!(count_safe(&reports, &rules), 2);
+    }
+
+    #[test]
+    fn several_levels_can_be_removed() {
+        let report = Report::parse("1 9 2 3 9 4");
+        let rules = SafetyRules { removable_levels: 2, ..SafetyRules::standard() };
+
+        assert_eq!(report.evaluate(&rules), Safety::SafeWithout(vec![1, 4]));
+        assert_eq!(report.evaluate(&SafetyRules::with_dampener()), Safety::Unsafe);
+    }
+
+    #[test]
+    fn linear_dampener_matches_removing_each_level() {
+        property::check(2000, |rng| {
+            let count = rng.range(1, 9);
+            let mut level = rng.range(1, 20);
+            let levels: Vec<String> = (0..count).map(|_| {
+                level += rng.range(-4, 5);
+                level.to_string()
+            }).collect();
+            levels.join(" ")
+        }, |input| {
+            let report = Report::parse(input);
+            let rules = SafetyRules::with_dampener();
+            let linear = report.evaluate(&rules);
+
+            property::assert_same("dampened safety", dampener_by_removing_each_level(&report, &rules), linear != Safety::Unsafe)?;
+            if let Safety::SafeWithout(removed) = &linear {
+                let mut levels = report.levels.clone();
+                levels.remove(removed[0]);
+                property::assert_same("safe after removal", Safety::Safe, Report { levels }.evaluate(&SafetyRules::standard()))?;
+            }
+            property::assert_same("k = 1 agrees with the general search", rules.directions().iter().any(|d| report.fewest_removals(&rules, *d, 1).is_some()), linear != Safety::Unsafe)
+        });
+    }
+
+    #[test]
+    fn verdicts_explain_the_first_problem() {
+        let rules = SafetyRules::with_dampener();
+
+        let verdict = Report::parse("1 3 2 4 5").verdict(&rules);
+        assert_eq!(verdict.safety, Safety::SafeWithout(vec![1]));
+        assert_eq!(verdict.violation, Some(Violation { index: 1, from: 3, to: 2, reason: Reason::DirectionChange }));
+
+        assert_eq!(Report::parse("8 6 4 4 1").verdict(&rules).violation.unwrap().reason, Reason::ZeroStep);
+        assert_eq!(Report::parse("1 2 7 8 9").verdict(&rules).violation.unwrap().reason, Reason::JumpTooLarge);
+        assert_eq!(Report::parse("7 6 4 2 1").verdict(&rules).violation, None);
+    }
+
+    #[test]
+    fn reports_can_be_explained_as_a_table() {
+        let reports = parse("7 6 4 2 1\n1 2 7 8 9\n1 3 2 4 5".to_string());
+        let table = explain(&reports);
+
+        assert!(table.contains("2 | 1 2 7 8 9 | unsafe               | 2 -> 7 at index 1 jump too large"));
+        assert!(table.contains("3 | 1 3 2 4 5 | safe without index 1 | 3 -> 2 at index 1 changes direction"));
+        assert!(table.ends_with("1 of 3 reports safe, 2 with the dampener"));
     }
 }
@@ -1,166 +1,238 @@
-﻿use std::fmt::{Debug, Display};
-use std::ops::Index;
-use nom::bytes::complete::{tag, take_until};
+﻿use std::fmt;
+use nom::bytes::complete::tag;
 use nom::IResult;
 use nom::sequence::{delimited, separated_pair};
-use nom::character::complete::char;
-use nom::combinator::{opt, peek};
-use crate::tools::parse_numbers_i32;
+use nom::character::complete::{char, digit1};
+use nom::combinator::{map_res, verify};
+use std::str::FromStr;
 
-pub fn part_one(input: String) -> impl Display {
-    let results = parse_out_operations(&input).unwrap().1;
-
-    let total = results.iter().map(|x| x.get_result()).sum::<i32>();
-    total
+pub fn parse(input: String) -> Memory {
+    parse_with(input, &ParseRules::puzzle())
 }
 
-pub fn part_two(input: String) -> impl Display {
-    let results = parse_out_operations_advanced(&input).unwrap().1;
+pub fn parse_with(input: String, rules: &ParseRules) -> Memory {
+    Memory { instructions: tokenize(&input, rules), dump: input }
+}
 
-    let total = results.iter().map(|x| x.get_result()).sum::<i32>();
-    total
+pub fn part_one(memory: &Memory) -> Result<i64, String> {
+    sum_results(&Interpreter::ignoring_conditionals().run(&memory.instructions))
 }
 
+pub fn part_two(memory: &Memory) -> Result<i64, String> {
+    sum_results(&Interpreter::new().run(&memory.instructions))
+}
 
-fn parse_out_operations(input: &str) -> IResult<&str, Vec<MultiplyOperation>> {
-    let mut operations: Vec<MultiplyOperation> = Vec::new();
-    let mut remaining_input = input;
-    let mut input = input;
+fn sum_results(operations: &Vec<MultiplyOperation>) -> Result<i64, String> {
+    operations.iter().try_fold(0i64, |total, operation| {
+        let result = operation.get_result().ok_or(format!("{} overflows", operation))?;
+        total.checked_add(result).ok_or(format!("sum overflows after adding {}", operation))
+    })
+}
 
-    loop {
+// How many digits a mul operand may have. The puzzle allows 1 to 3.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ParseRules {
+    min_digits: usize,
+    max_digits: usize
+}
 
+impl ParseRules {
+    pub fn puzzle() -> Self {
+        ParseRules { min_digits: 1, max_digits: 3 }
+    }
 
-        let (input, _) = take_until("mul(")(remaining_input)?;
-        let (input, _) = tag("mul")(input)?;
-        let (input, operation) = opt(parse_out_next)(input)?;
-        match operation {
-            None => {}
-            Some(operation_value) => {
-                    operations.push(operation_value);
-            }
+    // Operands are read into an i64, so anything over 18 digits could not be parsed anyway.
+    pub fn digits(min_digits: usize, max_digits: usize) -> Result<Self, String> {
+        if min_digits == 0 || min_digits > max_digits || max_digits > 18 {
+            return Err(format!("operands need between 1 and 18 digits, got {} to {}", min_digits, max_digits));
         }
-        remaining_input = input;
-        let check = check_pattern(remaining_input);
 
-        let peek_result = check;
-        if(!peek_result.is_ok()){
-            break;
-        }
+        Ok(ParseRules { min_digits, max_digits })
+    }
+}
+
+// Prints the dump with each multiplication coloured by whether it counts towards part two.
+pub fn explain(memory: &Memory) -> String {
+    let traced = Interpreter::new().trace(&memory.instructions);
+    let mut output = String::new();
+    let mut position = 0;
+
+    for (instruction, counted) in &traced {
+        let source = &memory.dump[instruction.offset..instruction.offset + instruction.length];
+        let colour = match (instruction.token, counted) {
+            (Token::Multiply(_), true) => COUNTED_COLOUR,
+            (Token::Multiply(_), false) => DISABLED_COLOUR,
+            _ => CONDITIONAL_COLOUR
+        };
+
+        output.push_str(&memory.dump[position..instruction.offset]);
+        output.push_str(&ansi_hex_color::colored(colour, "", source));
+        position = instruction.offset + instruction.length;
     }
+    output.push_str(&memory.dump[position..]);
+
+    let counted = traced.iter().filter(|(i, counted)| matches!(i.token, Token::Multiply(_)) && *counted).count();
+    let disabled = traced.iter().filter(|(i, counted)| matches!(i.token, Token::Multiply(_)) && !*counted).count();
+    output.push_str(&format!("\n\n{}, {}, {}",
+        ansi_hex_color::colored(COUNTED_COLOUR, "", &format!("{} counted", counted)),
+        ansi_hex_color::colored(DISABLED_COLOUR, "", &format!("{} disabled", disabled)),
+        ansi_hex_color::colored(CONDITIONAL_COLOUR, "", &format!("{} do/don't", traced.len() - counted - disabled))));
 
-    Ok((input, operations))
+    output
 }
 
+const COUNTED_COLOUR: &str = "#00C853";
+const DISABLED_COLOUR: &str = "#FF5252";
+const CONDITIONAL_COLOUR: &str = "#FFD600";
 
-fn parse_out_operations_advanced(input: &str) -> IResult<&str, Vec<MultiplyOperation>> {
-    let mut operations: Vec<MultiplyOperation> = Vec::new();
-    let mut remaining_input = input;
-    let mut input = input;
-    let mut enabled = true;
-    loop {
-
-        if(enabled){
-            let dont_match = remaining_input.find("don't()");
-            let mut dont_index = 0;
-            match dont_match {
-                None => {}
-                Some(v) => {
-                    dont_index = v;
-                }
-            }
+pub struct Memory {
+    instructions: Vec<Instruction>,
+    dump: String
+}
+
+// A recognized token and the byte range it came from in the memory dump.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Instruction {
+    token: Token,
+    offset: usize,
+    length: usize
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Token {
+    Multiply(MultiplyOperation),
+    Do,
+    Dont
+}
 
+// Every instruction the lexer knows about. Adding one is a new parser here and a new arm in Interpreter::execute.
+type InstructionParser = for<'a> fn(&'a str, &ParseRules) -> IResult<&'a str, Token>;
+const INSTRUCTIONS: [InstructionParser; 3] = [parse_multiply, parse_do, parse_dont];
 
-            let mul_match = remaining_input.find("mul(");
-            match mul_match {
-                None => {}
-                Some(index) => {
-                    if(dont_index > index) {
-                        // its a mul first. We dont do anything
-                    }
-                    else if(dont_index != 0 && dont_index < index){
-                        enabled = false;
-                        let (input, _) = take_until("don't()")(remaining_input)?;
-                        let (input, _) = tag("don't()")(input)?;
-                        remaining_input = input;
-                    }
-                }
+// Single pass over the dump: at each position try every instruction, skip one character if none match.
+fn tokenize(input: &str, rules: &ParseRules) -> Vec<Instruction> {
+    let mut instructions: Vec<Instruction> = Vec::new();
+    let mut remaining_input = input;
+
+    while let Some(next_char) = remaining_input.chars().next() {
+        let matched = INSTRUCTIONS.iter().find_map(|instruction| instruction(remaining_input, rules).ok());
+        match matched {
+            Some((rest, token)) => {
+                let offset = input.len() - remaining_input.len();
+                instructions.push(Instruction { token, offset, length: remaining_input.len() - rest.len() });
+                remaining_input = rest;
             }
-            // Check if mul( or don't() occur first.
-            // If don't(), then consume up to it and disable
-            // If mul( then don't do anything
-        }
-        else {
-            let do_match = remaining_input.find("do()");
-            let mut do_index = 0;
-            match do_match {
-                None => {}
-                Some(v) => {
-                    enabled = true;
-                    let (input, _) = take_until("do()")(remaining_input)?;
-                    let (input, _) = tag("do()")(input)?;
-                    remaining_input = input;
-                }
+            None => {
+                remaining_input = &remaining_input[next_char.len_utf8()..];
             }
         }
+    }
 
-        let (input, _) = take_until("mul(")(remaining_input)?;
-        let (input, _) = tag("mul")(input)?;
-        let (input, operation) = opt(parse_out_next)(input)?;
-        match operation {
-            None => {}
-            Some(operation_value) => {
-                if(enabled) {
-                    operations.push(operation_value);
-                }
-            }
-        }
-        remaining_input = input;
-        let check = check_pattern(remaining_input);
+    instructions
+}
 
-        let peek_result = check;
-        if(!peek_result.is_ok()){
-            break;
-        }
-    }
+fn parse_multiply<'a>(input: &'a str, rules: &ParseRules) -> IResult<&'a str, Token> {
+    let (input, _) = tag("mul")(input)?;
+    let (input, operation) = parse_out_next(input, rules)?;
+
+    Ok((input, Token::Multiply(operation)))
+}
+
+fn parse_do<'a>(input: &'a str, _rules: &ParseRules) -> IResult<&'a str, Token> {
+    let (input, _) = tag("do()")(input)?;
+
+    Ok((input, Token::Do))
+}
+
+fn parse_dont<'a>(input: &'a str, _rules: &ParseRules) -> IResult<&'a str, Token> {
+    let (input, _) = tag("don't()")(input)?;
 
-    Ok((input, operations))
+    Ok((input, Token::Dont))
 }
 
-fn check_pattern(input: &str) -> IResult<&str, &str> {
-    peek(take_until("mul"))(input)
+struct Interpreter {
+    enabled: bool,
+    respect_conditionals: bool
 }
 
+impl Interpreter {
+    fn new() -> Self {
+        Interpreter { enabled: true, respect_conditionals: true }
+    }
+
+    fn ignoring_conditionals() -> Self {
+        Interpreter { enabled: true, respect_conditionals: false }
+    }
+
+    fn run(mut self, instructions: &Vec<Instruction>) -> Vec<MultiplyOperation> {
+        instructions.iter().filter_map(|instruction| self.execute(&instruction.token)).collect()
+    }
 
-fn parse_out_next(input: &str) -> IResult<&str, MultiplyOperation> {
+    // Pairs every instruction with whether it produced a counted multiplication.
+    fn trace(mut self, instructions: &Vec<Instruction>) -> Vec<(Instruction, bool)> {
+        instructions.iter().map(|instruction| (*instruction, self.execute(&instruction.token).is_some())).collect()
+    }
+
+    fn execute(&mut self, token: &Token) -> Option<MultiplyOperation> {
+        match token {
+            Token::Multiply(operation) => self.enabled.then_some(*operation),
+            Token::Do => {
+                self.enabled = true;
+                None
+            }
+            Token::Dont => {
+                self.enabled = !self.respect_conditionals;
+                None
+            }
+        }
+    }
+}
+
+
+fn parse_out_next<'a>(input: &'a str, rules: &ParseRules) -> IResult<&'a str, MultiplyOperation> {
+    let operand = |i| parse_operand(i, rules);
     let (input_line, (left,right)) = delimited(
         char('('),
-        separated_pair(parse_numbers_i32, char(','), parse_numbers_i32),
+        separated_pair(operand, char(','), operand),
         char(')')
     )(input)?;
 
     Ok((input_line, MultiplyOperation { left: left, right: right }))
 }
 
-#[derive(Debug, Clone, Copy)]
+fn parse_operand<'a>(input: &'a str, rules: &ParseRules) -> IResult<&'a str, i64> {
+    map_res(
+        verify(digit1, |digits: &str| digits.len() >= rules.min_digits && digits.len() <= rules.max_digits),
+        i64::from_str
+    )(input)
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 struct MultiplyOperation{
-    left: i32,
-    right: i32
+    left: i64,
+    right: i64
 }
 
 impl MultiplyOperation {
-    fn get_result(&self) -> i32 {
-        &self.left * &self.right
+    fn get_result(&self) -> Option<i64> {
+        self.left.checked_mul(self.right)
+    }
+}
+
+impl fmt::Display for MultiplyOperation {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "mul({},{})", self.left, self.right)
     }
 }
 
 #[cfg(test)]
 mod tests {
-    use crate::days::day_03;
+    use crate::days::day_03::{explain, parse, parse_with, part_one, tokenize, Instruction, Interpreter, MultiplyOperation, ParseRules, Token};
 
     #[test]
     fn multiply_operations_can_be_parsed() {
         let input = r#"xmul(2,4)%&mul[3,7]!@^do_not_mul(5,5)+mul(32,64]then(mul(11,8)mul(8,5))"#;
-        let results = day_03::parse_out_operations(input).unwrap().1;
+        let results = Interpreter::ignoring_conditionals().run(&tokenize(input, &ParseRules::puzzle()));
 
         assert_eq!(results.len(), 4);
     }
@@ -168,18 +240,79 @@ mod tests {
     #[test]
     fn multiply_operations_can_be_summed() {
         let input = r#"xmul(2,4)%&mul[3,7]!@^do_not_mul(5,5)+mul(32,64]then(mul(11,8)mul(8,5))"#;
-        let results = day_03::parse_out_operations(input).unwrap().1;
+        let results = Interpreter::ignoring_conditionals().run(&tokenize(input, &ParseRules::puzzle()));
 
-        let total = results.iter().map(|x| x.get_result()).sum::<i32>();
+        let total = results.iter().map(|x| x.get_result().unwrap()).sum::<i64>();
         assert_eq!(total, 161);
     }
 
     #[test]
     fn multiply_operations_can_be_summed_with_advanced() {
         let input = r#"xmul(2,4)&mul[3,7]!^don't()_mul(5,5)+mul(32,64](mul(11,8)undo()?mul(8,5))"#;
-        let results = day_03::parse_out_operations_advanced(input).unwrap().1;
+        let results = Interpreter::new().run(&tokenize(input, &ParseRules::puzzle()));
 
-        let total = results.iter().map(|x| x.get_result()).sum::<i32>();
+        let total = results.iter().map(|x| x.get_result().unwrap()).sum::<i64>();
         assert_eq!(total, 48);
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn memory_can_be_tokenized() {
+        let input = r#"don't()mul(2,4)do_mul(3,3]do()mul(1,1)"#;
+        let tokens:Vec<Token> = tokenize(input, &ParseRules::puzzle()).iter().map(|i| i.token).collect();
+
+        assert_eq!(tokens, vec![
+            Token::Dont,
+            Token::Multiply(MultiplyOperation { left: 2, right: 4 }),
+            Token::Do,
+            Token::Multiply(MultiplyOperation { left: 1, right: 1 })
+        ]);
+    }
+
+    #[test]
+    fn instructions_keep_their_source_span() {
+        let input = r#"xmul(2,4)%&don't()"#;
+        let instructions = tokenize(input, &ParseRules::puzzle());
+
+        assert_eq!(instructions[0], Instruction { token: Token::Multiply(MultiplyOperation { left: 2, right: 4 }), offset: 1, length: 8 });
+        assert_eq!(instructions[1], Instruction { token: Token::Dont, offset: 11, length: 7 });
+        assert_eq!(&input[1..9], "mul(2,4)");
+    }
+
+    #[test]
+    fn trace_marks_disabled_multiplications() {
+        let memory = parse(r#"mul(2,4)don't()mul(5,5)"#.to_string());
+        let traced: Vec<bool> = Interpreter::new().trace(&memory.instructions).iter().map(|(_, counted)| *counted).collect();
+
+        assert_eq!(traced, vec![true, false, false]);
+        assert!(explain(&memory).contains("1 counted"));
+    }
+
+    #[test]
+    fn dont_at_the_start_disables_multiplication() {
+        let input = r#"don't()mul(2,4)"#;
+
+        assert!(Interpreter::new().run(&tokenize(input, &ParseRules::puzzle())).is_empty());
+    }
+
+    #[test]
+    fn operands_outside_the_digit_limit_are_ignored() {
+        let input = r#"mul(1234,2)mul(-2,3)mul(12,345)"#;
+
+        let instructions = tokenize(input, &ParseRules::puzzle());
+        assert_eq!(instructions.len(), 1);
+
+        let wide = parse_with(input.to_string(), &ParseRules::digits(1, 4).unwrap());
+        assert_eq!(wide.instructions.len(), 2);
+        assert!(ParseRules::digits(3, 2).is_err());
+        assert!(ParseRules::digits(1, 19).is_err());
+    }
+
+    #[test]
+    fn overflowing_results_are_reported() {
+        let memory = parse("mul(999,999)".to_string());
+        assert_eq!(part_one(&memory), Ok(998001));
+
+        let memory = parse_with("mul(9999999999,9999999999)".to_string(), &ParseRules::digits(1, 10).unwrap());
+        assert_eq!(part_one(&memory), Err("mul(9999999999,9999999999) overflows".to_string()));
+    }
+}
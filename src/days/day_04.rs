@@ -1,25 +1,49 @@
-﻿use std::collections::HashMap;
+﻿use std::cmp::Reverse;
+use std::collections::{HashMap, HashSet, VecDeque};
 use std::fmt::Display;
-use crate::domain::point::Point;
+use crate::domain::point::{Point, EAST, NORTH, SOUTH, WEST};
 use crate::domain::point::NORTHEAST;
 use crate::domain::point::SOUTHEAST;
 use crate::domain::point::NORTHWEST;
 use crate::domain::point::SOUTHWEST;
-pub fn part_one(input: String) -> impl Display {
-    let grid = WordGrid::parse(input);
+pub fn parse(input: String) -> WordGrid {
+    WordGrid::parse(input)
+}
 
+pub fn part_one(grid: &WordGrid) -> impl Display {
     let result = grid.search_word("XMAS");
     result
 }
 
-pub fn part_two(input: String) -> impl Display {
-    let grid = WordGrid::parse(input);
-
+pub fn part_two(grid: &WordGrid) -> impl Display {
     let result = grid.search_cross_word();
     result
 }
 
-struct WordGrid {
+// Shows where each part's matches are, one colour per match.
+pub fn explain(grid: &WordGrid) -> String {
+    let words = WordSearch::new(vec![Pattern::word("XMAS")]).find_all(grid);
+    let crosses = WordSearch::new(vec![Pattern::stamp("M.S\n.A.\nM.S")]).find_all(grid);
+    let style = RenderStyle::Ansi { colour_per_match: true };
+
+    format!("XMAS: {} matches\n{}\n\nX-MAS: {} matches\n{}",
+        words.len(), grid.render(&words, style),
+        crosses.len(), grid.render(&crosses, style))
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RenderStyle {
+    // Matched letters coloured, the rest dimmed. Without a colour per match every match shares one highlight.
+    Ansi { colour_per_match: bool },
+    // Matched letters kept and everything else masked with '.', for writing to files
+    Plain
+}
+
+const HIGHLIGHT_COLOUR: &str = "#00C853";
+const DIMMED_COLOUR: &str = "#616161";
+const MATCH_COLOURS: [&str; 6] = ["#FF5252", "#FFD600", "#00C853", "#40C4FF", "#E040FB", "#FF9100"];
+
+pub struct WordGrid {
     grid: HashMap<Point, char>,
     size: Point
 }
@@ -43,92 +67,348 @@ impl WordGrid {
         WordGrid { grid: map, size: total_size }
     }
 
-    fn search_cross_word(&self) -> i32 {
-        // search for A
-        // If we find one, then look for in the four diagonal.
-        // If its successful then add the points to a list to avoid finding the same one again
-        let mut count = 0;
+    fn get(&self, point: &Point) -> Option<char> {
+        self.grid.get(point).copied()
+    }
 
+    // Every straight line of cells running in `direction`, each starting at the grid's edge.
+    fn lines(&self, direction: Point) -> Vec<Vec<Point>> {
+        let mut lines: Vec<Vec<Point>> = Vec::new();
         for y in (0..self.size.y).rev() {
             for x in 0..self.size.x {
-                let current_point = Point::new(x, y);
-                let current_letter = *self.grid.get(&current_point).unwrap();
-                if 'A' == current_letter {
-                    if(self.check_diagonal(current_point + NORTHEAST, current_point + SOUTHWEST) && self.check_diagonal(current_point + NORTHWEST, current_point + SOUTHEAST)){
-                        count += 1;
-                    }
+                let start = Point::new(x, y);
+                if self.grid.contains_key(&(start - direction)) {
+                    continue;
+                }
+                let mut line = Vec::new();
+                let mut current = start;
+                while self.grid.contains_key(&current) {
+                    line.push(current);
+                    current = current + direction;
                 }
+                lines.push(line);
             }
         }
 
-        count
+        lines
     }
 
-    fn check_diagonal(&self, start: Point, end:Point) -> bool {
-        if(!self.grid.contains_key(&start) || !self.grid.contains_key(&end)){
-            return false;
+    // Cells covered by several matches take the colour of the first one.
+    pub fn render(&self, matches: &[Match], style: RenderStyle) -> String {
+        let mut owners: HashMap<Point, usize> = HashMap::new();
+        for (index, m) in matches.iter().enumerate() {
+            for cell in &m.cells {
+                owners.entry(*cell).or_insert(index);
+            }
         }
-        let start_letter = *self.grid.get(&start).unwrap();
-        let end_letter = *self.grid.get(&end).unwrap();
 
-        if(start_letter == 'M' && end_letter == 'S')
-        {
-            return true;
-        }
-        if(start_letter == 'S' && end_letter == 'M')
-        {
-            return true;
+        let mut lines: Vec<String> = Vec::new();
+        for y in (0..self.size.y).rev() {
+            let mut line = String::new();
+            for x in 0..self.size.x {
+                let point = Point::new(x, y);
+                let letter = self.get(&point).unwrap().to_string();
+                let owner = owners.get(&point);
+                match (style, owner) {
+                    (RenderStyle::Plain, Some(_)) => line.push_str(&letter),
+                    (RenderStyle::Plain, None) => line.push('.'),
+                    (RenderStyle::Ansi { colour_per_match: true }, Some(index)) =>
+                        line.push_str(&ansi_hex_color::colored(MATCH_COLOURS[index % MATCH_COLOURS.len()], "", &letter)),
+                    (RenderStyle::Ansi { colour_per_match: false }, Some(_)) =>
+                        line.push_str(&ansi_hex_color::colored(HIGHLIGHT_COLOUR, "", &letter)),
+                    (RenderStyle::Ansi { .. }, None) => line.push_str(&ansi_hex_color::colored(DIMMED_COLOUR, "", &letter))
+                }
+            }
+            lines.push(line);
         }
 
-        false
+        lines.join("\n")
+    }
+
+    fn search_cross_word(&self) -> i32 {
+        let search = WordSearch::new(vec![Pattern::stamp("M.S\n.A.\nM.S")]);
+        search.count(self, CountMode::Overlapping) as i32
     }
 
     fn search_word(&self, word: &str) -> i32 {
-        let first_letter = word.chars().nth(0).unwrap();
-        let mut count = 0;
-        for y in (0..self.size.y).rev() {
-            for x in 0..self.size.x {
-                let current_point = Point::new(x,y);
-                if first_letter == *self.grid.get(&Point::new(x,y)).unwrap() {
+        let search = WordSearch::new(vec![Pattern::word(word)]);
+        search.count(self, CountMode::Overlapping) as i32
+    }
+}
 
-                    let neighbors = Point::new(x,y).get_neighbors();
-                    for neighbor in neighbors {
-                        count += self.search_for_next_letter_recursive(Point::new(x, y), current_point - neighbor, 1, word);
-                    }
-                    // count += self.search_for_next_letter_recursive(Point::new(x, y), 1, word);
-                    // search the next letters
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CountMode {
+    Overlapping,
+    // Matches are taken in scan order and skipped if they reuse a cell an earlier match claimed
+    NonOverlapping
+}
+
+// Where template columns and rows point on the grid. Rows run down the page, which is SOUTH since the grid's y grows upwards.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Orientation {
+    across: Point,
+    down: Point
+}
+
+impl Orientation {
+    fn place(&self, column: i32, row: i32) -> Point {
+        self.across.scale(column) + self.down.scale(row)
+    }
+}
+
+// A word or 2D stamp to search for. Wildcard cells ('.' in a stamp) are left out of `cells`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Pattern {
+    cells: Vec<(i32, i32, char)>
+}
+
+impl Pattern {
+    pub fn word(word: &str) -> Self {
+        Pattern { cells: word.chars().enumerate().map(|(column, letter)| (column as i32, 0, letter)).collect() }
+    }
+
+    pub fn stamp(template: &str) -> Self {
+        let mut cells = Vec::new();
+        for (row, line) in template.lines().enumerate() {
+            for (column, letter) in line.chars().enumerate() {
+                if letter != '.' {
+                    cells.push((column as i32, row as i32, letter));
+                }
+            }
+        }
+
+        Pattern { cells }
+    }
+
+    fn as_word(&self) -> Option<Vec<char>> {
+        let contiguous = self.cells.iter().enumerate().all(|(index, (column, row, _))| *column == index as i32 && *row == 0);
+        (contiguous && !self.cells.is_empty()).then(|| self.cells.iter().map(|(_, _, letter)| *letter).collect())
+    }
+
+    // Words are read along any of the eight directions; stamps are rotated and mirrored.
+    // Orientations that land on the same cells (palindromes, symmetric stamps) are only kept once.
+    fn placements(&self) -> Vec<(Orientation, Vec<(Point, char)>)> {
+        let single_row = self.cells.iter().all(|(_, row, _)| *row == 0);
+        let acrosses = if single_row {
+            DIRECTIONS.to_vec()
+        } else {
+            vec![EAST, NORTH, WEST, SOUTH]
+        };
+
+        let mut placements: Vec<(Orientation, Vec<(Point, char)>)> = Vec::new();
+        let mut seen: HashSet<Vec<(Point, char)>> = HashSet::new();
+        for across in acrosses {
+            let clockwise = Point::new(across.y, -across.x);
+            for down in [clockwise, clockwise.scale(-1)] {
+                let orientation = Orientation { across, down };
+                let Some(&(anchor_column, anchor_row, _)) = self.cells.first() else {
+                    continue;
+                };
+                let anchor = orientation.place(anchor_column, anchor_row);
+                let cells: Vec<(Point, char)> = self.cells.iter()
+                    .map(|(column, row, letter)| (orientation.place(*column, *row) - anchor, *letter))
+                    .collect();
+
+                let mut key = cells.clone();
+                let lowest = key.iter().map(|(p, _)| *p).min().unwrap();
+                key.iter_mut().for_each(|(p, _)| *p = *p - lowest);
+                key.sort();
+                if seen.insert(key) {
+                    placements.push((orientation, cells));
                 }
             }
         }
 
-        count
+        placements
+    }
+}
+
+fn find_placements(index: usize, pattern: &Pattern, grid: &WordGrid) -> Vec<Match> {
+    let mut matches: Vec<Match> = Vec::new();
+    let placements = pattern.placements();
+
+    for y in (0..grid.size.y).rev() {
+        for x in 0..grid.size.x {
+            let start = Point::new(x, y);
+            for (orientation, cells) in &placements {
+                if cells.iter().all(|(offset, letter)| grid.get(&(start + *offset)) == Some(*letter)) {
+                    matches.push(Match {
+                        pattern: index,
+                        start,
+                        orientation: *orientation,
+                        cells: cells.iter().map(|(offset, _)| start + *offset).collect()
+                    });
+                }
+            }
+        }
+    }
+
+    matches
+}
+
+// Scan order: pattern, then top to bottom and left to right, then the order placements() tries orientations.
+fn sort_matches(matches: &mut Vec<Match>) {
+    matches.sort_by_key(|m| {
+        let across = DIRECTIONS.iter().position(|d| *d == m.orientation.across).unwrap();
+        let clockwise = Point::new(m.orientation.across.y, -m.orientation.across.x);
+        (m.pattern, Reverse(m.start.y), m.start.x, across, m.orientation.down != clockwise)
+    });
+}
+
+const DIRECTIONS: [Point; 8] = [EAST, NORTHEAST, NORTH, NORTHWEST, WEST, SOUTHWEST, SOUTH, SOUTHEAST];
+
+// Aho-Corasick automaton over the word list. Running it forwards and backwards along every row,
+// column and diagonal finds every word in all eight directions in one pass per line.
+struct WordAutomaton {
+    transitions: Vec<HashMap<char, usize>>,
+    fail: Vec<usize>,
+    outputs: Vec<Vec<(usize, usize)>>,
+    palindromes: HashSet<usize>
+}
+
+impl WordAutomaton {
+    fn build(words: &Vec<(usize, Vec<char>)>) -> Self {
+        let mut automaton = WordAutomaton { transitions: vec![HashMap::new()], fail: vec![0], outputs: vec![Vec::new()], palindromes: HashSet::new() };
+
+        for (index, word) in words {
+            let mut state = 0;
+            for letter in word {
+                state = match automaton.transitions[state].get(letter) {
+                    Some(next) => *next,
+                    None => {
+                        automaton.transitions.push(HashMap::new());
+                        automaton.fail.push(0);
+                        automaton.outputs.push(Vec::new());
+                        let next = automaton.transitions.len() - 1;
+                        automaton.transitions[state].insert(*letter, next);
+                        next
+                    }
+                };
+            }
+            automaton.outputs[state].push((*index, word.len()));
+            if word.iter().eq(word.iter().rev()) {
+                automaton.palindromes.insert(*index);
+            }
+        }
+
+        let mut queue: VecDeque<usize> = automaton.transitions[0].values().copied().collect();
+        while let Some(state) = queue.pop_front() {
+            let edges: Vec<(char, usize)> = automaton.transitions[state].iter().map(|(c, s)| (*c, *s)).collect();
+            for (letter, next) in edges {
+                let fallback = automaton.next(automaton.fail[state], letter);
+                automaton.fail[next] = fallback;
+                let inherited = automaton.outputs[fallback].clone();
+                automaton.outputs[next].extend(inherited);
+                queue.push_back(next);
+            }
+        }
+
+        automaton
     }
 
-    fn search_for_next_letter_recursive(&self, current_spot: Point, direction: Point, word_index: usize, word: &str) -> i32 {
-        let mut count = 0;
-        let next_point = current_spot + direction;
-        let letter = word.chars().nth(word_index).unwrap();
-        if(!self.grid.contains_key(&next_point)) {
-            return 0;
+    fn next(&self, mut state: usize, letter: char) -> usize {
+        loop {
+            if let Some(next) = self.transitions[state].get(&letter) {
+                return *next;
+            }
+            if state == 0 {
+                return 0;
+            }
+            state = self.fail[state];
         }
+    }
 
-        if letter == *self.grid.get(&next_point).unwrap() {
-            if word_index == word.len() - 1 {
-                // End of word. LFG
-                count += 1;
+    fn search(&self, grid: &WordGrid) -> Vec<Match> {
+        let mut matches: Vec<Match> = Vec::new();
+
+        for direction in [EAST, NORTHEAST, NORTH, NORTHWEST] {
+            for line in grid.lines(direction) {
+                self.scan(grid, &line, direction, false, &mut matches);
+                let reversed: Vec<Point> = line.iter().rev().copied().collect();
+                self.scan(grid, &reversed, direction.scale(-1), true, &mut matches);
             }
-            else {
-                count += self.search_for_next_letter_recursive(next_point, direction, word_index + 1, word);
+        }
+
+        matches
+    }
+
+    fn scan(&self, grid: &WordGrid, line: &Vec<Point>, direction: Point, reversed: bool, matches: &mut Vec<Match>) {
+        let orientation = Orientation { across: direction, down: Point::new(direction.y, -direction.x) };
+        let mut state = 0;
+
+        for (position, point) in line.iter().enumerate() {
+            state = self.next(state, grid.get(point).unwrap());
+            for (pattern, length) in &self.outputs[state] {
+                // A palindrome read backwards covers the same cells, as does a single letter in any direction
+                if reversed && self.palindromes.contains(pattern) {
+                    continue;
+                }
+                if *length == 1 && direction != EAST {
+                    continue;
+                }
+                let cells: Vec<Point> = line[position + 1 - length..=position].to_vec();
+                matches.push(Match { pattern: *pattern, start: cells[0], orientation, cells });
             }
         }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Match {
+    pattern: usize,
+    start: Point,
+    orientation: Orientation,
+    cells: Vec<Point>
+}
 
+pub struct WordSearch {
+    patterns: Vec<Pattern>
+}
+
+impl WordSearch {
+    pub fn new(patterns: Vec<Pattern>) -> Self {
+        WordSearch { patterns }
+    }
+
+    // Plain words all go through one automaton pass over the grid's lines; stamps are checked cell by cell.
+    pub fn find_all(&self, grid: &WordGrid) -> Vec<Match> {
+        let words: Vec<(usize, Vec<char>)> = self.patterns.iter().enumerate()
+            .filter_map(|(index, pattern)| pattern.as_word().map(|word| (index, word)))
+            .collect();
 
-        count
+        let mut matches = if words.is_empty() { Vec::new() } else { WordAutomaton::build(&words).search(grid) };
+        for (index, pattern) in self.patterns.iter().enumerate() {
+            if pattern.as_word().is_none() {
+                matches.extend(find_placements(index, pattern, grid));
+            }
+        }
+
+        sort_matches(&mut matches);
+        matches
+    }
+
+    pub fn count(&self, grid: &WordGrid, mode: CountMode) -> usize {
+        let matches = self.find_all(grid);
+        match mode {
+            CountMode::Overlapping => matches.len(),
+            CountMode::NonOverlapping => {
+                let mut claimed: HashSet<Point> = HashSet::new();
+                matches.iter().filter(|m| {
+                    if m.cells.iter().any(|c| claimed.contains(c)) {
+                        return false;
+                    }
+                    claimed.extend(m.cells.iter());
+                    true
+                }).count()
+            }
+        }
     }
 }
 #[cfg(test)]
 mod tests {
-    use super::{WordGrid};
+    use crate::domain::point::{Point, EAST, SOUTH, WEST};
+    use crate::property;
+    use super::{explain, find_placements, sort_matches, CountMode, Orientation, Pattern, RenderStyle, WordGrid, WordSearch};
 
     #[test]
     fn finding_xmas_in_word_grid() {
@@ -165,4 +445,81 @@ MXMXAXMASX"#;
         let result = word_grid.search_cross_word();
         assert_eq!(result, 9);
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn matches_report_start_and_orientation() {
+        let word_grid = WordGrid::parse("SAMX\n.XY.".to_string());
+        let matches = WordSearch::new(vec![Pattern::word("XMAS")]).find_all(&word_grid);
+
+        assert_eq!(matches.len(), 1);
+        assert_eq!(matches[0].start, Point::new(3, 1));
+        assert_eq!(matches[0].orientation.across, WEST);
+    }
+
+    #[test]
+    fn several_words_and_stamps_can_be_searched_together() {
+        let word_grid = WordGrid::parse("ABA\nBAB\nABA".to_string());
+        let search = WordSearch::new(vec![
+            Pattern::word("AB"),
+            Pattern::stamp(".B.\nBAB\n.B."),
+        ]);
+        let matches = search.find_all(&word_grid);
+
+        assert_eq!(matches.iter().filter(|m| m.pattern == 0).count(), 12);
+        assert_eq!(matches.iter().filter(|m| m.pattern == 1).count(), 1);
+        assert_eq!(matches.iter().find(|m| m.pattern == 1).unwrap().orientation, Orientation { across: EAST, down: SOUTH });
+    }
+
+    #[test]
+    fn non_overlapping_mode_skips_shared_cells() {
+        let word_grid = WordGrid::parse("ABABA".to_string());
+        let search = WordSearch::new(vec![Pattern::word("ABA")]);
+
+        assert_eq!(search.count(&word_grid, CountMode::Overlapping), 2);
+        assert_eq!(search.count(&word_grid, CountMode::NonOverlapping), 1);
+    }
+
+    #[test]
+    fn automaton_matches_cell_by_cell_search() {
+        property::check(500, |rng| {
+            let size = rng.range(1, 8);
+            let letters = ['A', 'B', 'C'];
+            let grid: Vec<String> = (0..size).map(|_| (0..size).map(|_| *rng.choose(&letters)).collect()).collect();
+            let words: Vec<String> = (0..rng.range(1, 5)).map(|_| (0..rng.range(1, 5)).map(|_| *rng.choose(&letters)).collect()).collect();
+            format!("{}\n\n{}", words.join(" "), grid.join("\n"))
+        }, |input| {
+            let mut split = input.split("\n\n");
+            let patterns: Vec<Pattern> = split.next().unwrap().split(' ').map(Pattern::word).collect();
+            let word_grid = WordGrid::parse(split.next().unwrap().to_string());
+
+            let mut expected = Vec::new();
+            for (index, pattern) in patterns.iter().enumerate() {
+                expected.extend(find_placements(index, pattern, &word_grid));
+            }
+            sort_matches(&mut expected);
+
+            property::assert_same("matches", expected, WordSearch::new(patterns).find_all(&word_grid))
+        });
+    }
+
+    #[test]
+    fn plain_rendering_masks_unmatched_cells() {
+        let word_grid = WordGrid::parse("XMASX\nAXMAS\nSAMXM".to_string());
+        let matches = WordSearch::new(vec![Pattern::word("XMAS")]).find_all(&word_grid);
+
+        assert_eq!(word_grid.render(&matches, RenderStyle::Plain), "XMAS.\n.XMAS\nSAMX.");
+    }
+
+    #[test]
+    fn ansi_rendering_colours_each_match() {
+        let word_grid = WordGrid::parse("XMAS.SAMX".to_string());
+        let matches = WordSearch::new(vec![Pattern::word("XMAS")]).find_all(&word_grid);
+
+        let per_match = word_grid.render(&matches, RenderStyle::Ansi { colour_per_match: true });
+        let single = word_grid.render(&matches, RenderStyle::Ansi { colour_per_match: false });
+        assert_ne!(per_match, single);
+        assert!(per_match.contains(&ansi_hex_color::colored("#FFD600", "", "S")));
+        assert!(single.contains(&ansi_hex_color::colored("#616161", "", ".")));
+        assert!(explain(&word_grid).starts_with("XMAS: 2 matches"));
+    }
+}
@@ -1,38 +1,82 @@
-﻿use std::fmt;
-use std::fmt::Display;
-use std::ops::Index;
+﻿use std::cmp::Ordering;
+use std::collections::{HashMap, HashSet};
+use std::fmt;
 use crate::tools::parse_numbers_i32;
 
-pub fn part_one(input: String) -> impl Display {
+pub fn parse(input: String) -> PrintQueue {
     let mut split = input.split("\n\n");
     let rules:Vec<Rule> = split.nth(0).unwrap().lines().map(|line| Rule::parse(line)).collect();
-    let rule_engine = RuleEngine { rules };
+    let rule_engine = RuleEngine::new(rules);
     let manual_updates:Vec<ManualUpdate> = split.nth(0).unwrap().lines().map(|line| ManualUpdate::parse(line)).collect();
 
+    PrintQueue { rule_engine, manual_updates }
+}
+
+pub fn part_one(queue: &PrintQueue) -> Result<i32, String> {
+    let rule_engine = &queue.rule_engine;
 
-    let sum = rule_engine.process_manual_updates(manual_updates).iter().map(|m| m.get_middle_number()).sum::<i32>();
-    sum
+    let sum = rule_engine.process_manual_updates(queue.manual_updates.clone()).iter().map(|m| m.get_middle_number()).sum::<i32>();
+    Ok(sum)
 }
 
-pub fn part_two(input: String) -> impl Display {
-    let mut split = input.split("\n\n");
-    let rules:Vec<Rule> = split.nth(0).unwrap().lines().map(|line| Rule::parse(line)).collect();
-    let rule_engine = RuleEngine { rules };
-    let manual_updates:Vec<ManualUpdate> = split.nth(0).unwrap().lines().map(|line| ManualUpdate::parse(line)).collect();
+pub fn part_two(queue: &PrintQueue) -> Result<i32, String> {
+    let rule_engine = &queue.rule_engine;
+    let mut total = 0;
 
+    for update in rule_engine.get_incorrect_manual_updates(queue.manual_updates.clone()) {
+        total += match rule_engine.sort(&update) {
+            Reordering::Unique(ordered) | Reordering::Ambiguous(ordered) => ordered.get_middle_number(),
+            Reordering::Cycle(pages) => return Err(format!("update {} can't be ordered: {}", update, describe_cycle(&pages)))
+        };
+    }
 
-    let passed = rule_engine.get_incorrect_manual_updates(manual_updates);
+    Ok(total)
+}
 
-    let mut updated_list:Vec<ManualUpdate> = Vec::new();
+// Lists every update with whether it was already correct, how it was reordered, and any ambiguity or cycle.
+pub fn explain(queue: &PrintQueue) -> String {
+    let rule_engine = &queue.rule_engine;
+    let mut lines: Vec<String> = Vec::new();
 
-    for update in passed {
-        updated_list.push(update.reorder_pages(&rule_engine.rules));
+    for update in &queue.manual_updates {
+        let violations = rule_engine.violations(update);
+        if violations.is_empty() {
+            lines.push(format!("{}: correct", update));
+            continue;
+        }
+
+        let broken: Vec<String> = violations.iter().map(|rule| rule.to_string()).collect();
+        let verdict = match rule_engine.reorder(update) {
+            Reordering::Unique(ordered) => format!("reordered to {}", ordered),
+            Reordering::Ambiguous(ordered) => format!("reordered to {} (ambiguous, the rules allow other orders)", ordered),
+            Reordering::Cycle(pages) => describe_cycle(&pages)
+        };
+        lines.push(format!("{}: breaks {}, {}", update, broken.join(" "), verdict));
     }
 
-    let total = updated_list.iter().map(|m| m.get_middle_number()).sum::<i32>();
+    lines.join("\n")
+}
+
+fn describe_cycle(pages: &Vec<i32>) -> String {
+    let path: Vec<String> = pages.iter().chain(pages.first()).map(|p| p.to_string()).collect();
+    format!("pages {} form a cycle", path.join(" -> "))
+}
 
-    total
+pub struct PrintQueue {
+    rule_engine: RuleEngine,
+    manual_updates: Vec<ManualUpdate>
 }
+
+impl PrintQueue {
+    pub fn rule_engine(&self) -> &RuleEngine {
+        &self.rule_engine
+    }
+
+    pub fn manual_updates(&self) -> &Vec<ManualUpdate> {
+        &self.manual_updates
+    }
+}
+
 #[derive(Debug, PartialEq, Eq, Hash, Clone, PartialOrd, Ord)]
 struct Rule {
     before_page: i32,
@@ -45,101 +89,196 @@ impl Rule {
 
         Rule { before_page: *values.iter().nth(0).unwrap(), after_page: *values.iter().nth(1).unwrap()}
     }
+}
 
-
-    fn does_rule_apply(&self, manual_update: &ManualUpdate) -> bool {
-        if(manual_update.pages.contains(&self.after_page) && manual_update.pages.contains(&self.before_page)){
-            return true;
-        }
-
-        false
-    }
-
-    fn does_rule_pass(&self, manual_update: &ManualUpdate) -> bool {
-        if !self.does_rule_apply(manual_update) {
-            return true;
-        }
-
-        if manual_update.pages.iter().position(|&r| r == self.before_page) < manual_update.pages.iter().position(|&r| r == self.after_page) {
-            return true
-        }
-
-        false
+impl fmt::Display for Rule {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}|{}", self.before_page, self.after_page)
     }
-
-
 }
 
 #[derive(Debug, PartialEq, Eq, Hash, Clone, PartialOrd, Ord)]
-struct ManualUpdate {
+pub struct ManualUpdate {
     pages: Vec<i32>
 }
 
 impl ManualUpdate {
-    fn parse(input_line: &str) -> Self {
+    pub fn parse(input_line: &str) -> Self {
         let values = input_line.split(",").map(|v| parse_numbers_i32(v).unwrap().1).collect();
 
         ManualUpdate { pages: values}
     }
-    fn print(&self) {
-        for x in &self.pages {
-            print!("{}, ",x);
-        }
-        println!("");
-    }
 
+    pub fn pages(&self) -> &Vec<i32> {
+        &self.pages
+    }
     fn get_middle_number(&self) -> i32 {
         let mid_index = (self.pages.len() - 1) / 2;
         *self.pages.iter().nth(mid_index).unwrap()
     }
+}
 
-    fn reorder_pages(&self, rules: &Vec<Rule>) -> Self {
-        let mut updated_pages = self.clone();
+impl fmt::Display for ManualUpdate {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let pages: Vec<String> = self.pages.iter().map(|p| p.to_string()).collect();
+        write!(f, "{}", pages.join(","))
+    }
+}
+
+// Result of ordering one update by the rules between its own pages.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Reordering {
+    Unique(ManualUpdate),
+    // The rules leave a choice somewhere, so another order would satisfy them too
+    Ambiguous(ManualUpdate),
+    // Each page must come before the next, and the last before the first
+    Cycle(Vec<i32>)
+}
+
+// The rules indexed both ways: each page's before-set for checking updates, and its successors as a
+// precedence graph for reordering them.
+pub struct RuleEngine {
+    predecessors: HashMap<i32, HashSet<i32>>,
+    successors: HashMap<i32, HashSet<i32>>
+}
+
+impl RuleEngine {
+    fn new(rules: Vec<Rule>) -> Self {
+        let mut predecessors: HashMap<i32, HashSet<i32>> = HashMap::new();
+        let mut successors: HashMap<i32, HashSet<i32>> = HashMap::new();
+        for rule in &rules {
+            predecessors.entry(rule.after_page).or_default().insert(rule.before_page);
+            successors.entry(rule.before_page).or_default().insert(rule.after_page);
+        }
 
-        for rule in rules {
-            if(rule.does_rule_pass(&updated_pages)) {
-                continue;
+        RuleEngine { predecessors, successors }
+    }
+
+    fn must_precede(&self, before: i32, after: i32) -> bool {
+        self.successors.get(&before).map_or(false, |pages| pages.contains(&after))
+    }
+
+    // Pages with no rule between them compare equal, so this is only a total order when the rules cover every pair.
+    pub fn compare(&self, left: i32, right: i32) -> Ordering {
+        if left == right {
+            Ordering::Equal
+        } else if self.must_precede(left, right) {
+            Ordering::Less
+        } else if self.must_precede(right, left) {
+            Ordering::Greater
+        } else {
+            Ordering::Equal
+        }
+    }
+
+    // Every pair of distinct pages is ordered one way only, and the order is transitive.
+    pub fn is_total_order(&self, pages: &Vec<i32>) -> bool {
+        for a in pages {
+            for b in pages {
+                if a == b {
+                    continue;
+                }
+                let a_before_b = self.must_precede(*a, *b);
+                if a_before_b == self.must_precede(*b, *a) {
+                    return false;
+                }
+                // Only a before b can break transitivity through b, so the other half of the pairs skip the scan
+                if !a_before_b {
+                    continue;
+                }
+                for c in pages {
+                    if a != c && self.must_precede(*b, *c) && !self.must_precede(*a, *c) {
+                        return false;
+                    }
+                }
             }
+        }
 
-            println!("{} {}", rule.before_page, rule.after_page);
-            // If the rule does not pass. Then we move the pages to make it work.else
-            // Take the before page and move it to the index before the after page. Pushing all other pages down one.
-            updated_pages.pages.remove(updated_pages.pages.iter().position(|p| p == &rule.before_page).unwrap());
-            updated_pages.pages.insert(updated_pages.pages.iter().position(|p| p == &rule.after_page).unwrap(), rule.before_page);
+        true
+    }
 
+    // Sorts with the comparator when it is a total order over the update's pages, otherwise falls back
+    // to the topological sort which copes with gaps and reports cycles.
+    pub fn sort(&self, manual_update: &ManualUpdate) -> Reordering {
+        if !self.is_total_order(&manual_update.pages) {
+            return self.reorder(manual_update);
         }
 
-        for rule in rules {
-            if(rule.does_rule_pass(&updated_pages)) {
-                continue;
+        let mut sorted = manual_update.clone();
+        sorted.pages.sort_by(|a, b| self.compare(*a, *b));
+        Reordering::Unique(sorted)
+    }
+
+    // Topological sort of the graph restricted to the update's pages. Ties go to the page that came first in
+    // the update so the result is stable, and any tie means the rules don't pin the order down.
+    fn reorder(&self, manual_update: &ManualUpdate) -> Reordering {
+        let mut remaining = manual_update.pages.clone();
+        let mut incoming: HashMap<i32, usize> = remaining.iter().map(|page| (*page, 0)).collect();
+        for before in &remaining {
+            for after in &remaining {
+                if self.must_precede(*before, *after) {
+                    *incoming.get_mut(after).unwrap() += 1;
+                }
             }
+        }
 
-            println!("{} {}", rule.before_page, rule.after_page);
-            // If the rule does not pass. Then we move the pages to make it work.else
-            // Take the before page and move it to the index before the after page. Pushing all other pages down one.
-            updated_pages.pages.remove(updated_pages.pages.iter().position(|p| p == &rule.before_page).unwrap());
-            updated_pages.pages.insert(updated_pages.pages.iter().position(|p| p == &rule.after_page).unwrap(), rule.before_page);
+        let mut ordered: Vec<i32> = Vec::new();
+        let mut ambiguous = false;
+        while !remaining.is_empty() {
+            let ready: Vec<usize> = remaining.iter().enumerate().filter(|(_, page)| incoming[page] == 0).map(|(i, _)| i).collect();
+            if ready.is_empty() {
+                return Reordering::Cycle(self.find_cycle(&remaining));
+            }
+            ambiguous |= ready.len() > 1;
 
+            let page = remaining.remove(ready[0]);
+            for after in &remaining {
+                if self.must_precede(page, *after) {
+                    *incoming.get_mut(after).unwrap() -= 1;
+                }
+            }
+            ordered.push(page);
         }
 
-        updated_pages
+        let ordered = ManualUpdate { pages: ordered };
+        if ambiguous { Reordering::Ambiguous(ordered) } else { Reordering::Unique(ordered) }
     }
-}
 
-struct RuleEngine {
-    rules: Vec<Rule>
-}
+    // Every page left over has a predecessor among the others, so walking predecessors must revisit a page.
+    fn find_cycle(&self, remaining: &Vec<i32>) -> Vec<i32> {
+        let mut path = vec![remaining[0]];
+        loop {
+            let current = *path.last().unwrap();
+            let predecessor = *remaining.iter().find(|page| self.must_precede(**page, current)).unwrap();
+            if let Some(index) = path.iter().position(|page| *page == predecessor) {
+                let mut cycle = path[index..].to_vec();
+                cycle.reverse();
+                return cycle;
+            }
+            path.push(predecessor);
+        }
+    }
 
-impl RuleEngine {
     fn does_manual_update_pass_rules(&self, manual_update: &ManualUpdate) -> bool {
+        self.violations(manual_update).is_empty()
+    }
 
-        for rule in &self.rules {
-            if(!rule.does_rule_pass(manual_update)){
-                return false;
+    // Every rule the update breaks: a page that appears after a page its before-set says must follow it.
+    fn violations(&self, manual_update: &ManualUpdate) -> Vec<Rule> {
+        let positions: HashMap<i32, usize> = manual_update.pages.iter().enumerate().map(|(index, page)| (*page, index)).collect();
+        let mut violated: Vec<Rule> = Vec::new();
+
+        for (index, page) in manual_update.pages.iter().enumerate() {
+            let Some(before_pages) = self.predecessors.get(page) else { continue };
+            for before_page in before_pages {
+                if positions.get(before_page).map_or(false, |position| *position > index) {
+                    violated.push(Rule { before_page: *before_page, after_page: *page });
+                }
             }
         }
 
-        true
+        violated.sort();
+        violated
     }
 
 
@@ -155,7 +294,8 @@ impl RuleEngine {
 
 #[cfg(test)]
 mod tests {
-    use super::{Rule, RuleEngine};
+    use std::cmp::Ordering;
+    use super::{describe_cycle, explain, parse, Reordering, Rule, RuleEngine};
     use super::{ManualUpdate};
 
     #[test]
@@ -191,7 +331,7 @@ mod tests {
 
         let mut split = input.split("\n\n");
         let rules:Vec<Rule> = split.nth(0).unwrap().lines().map(|line| Rule::parse(line)).collect();
-        let rule_engine = RuleEngine { rules };
+        let rule_engine = RuleEngine::new(rules);
         let manual_updates:Vec<ManualUpdate> = split.nth(0).unwrap().lines().map(|line| ManualUpdate::parse(line)).collect();
 
 
@@ -233,7 +373,7 @@ mod tests {
 
         let mut split = input.split("\n\n");
         let rules:Vec<Rule> = split.nth(0).unwrap().lines().map(|line| Rule::parse(line)).collect();
-        let rule_engine = RuleEngine { rules };
+        let rule_engine = RuleEngine::new(rules);
         let manual_updates:Vec<ManualUpdate> = split.nth(0).unwrap().lines().map(|line| ManualUpdate::parse(line)).collect();
 
 
@@ -274,7 +414,7 @@ mod tests {
 
         let mut split = input.split("\n\n");
         let rules:Vec<Rule> = split.nth(0).unwrap().lines().map(|line| Rule::parse(line)).collect();
-        let rule_engine = RuleEngine { rules };
+        let rule_engine = RuleEngine::new(rules);
         let manual_updates:Vec<ManualUpdate> = split.nth(0).unwrap().lines().map(|line| ManualUpdate::parse(line)).collect();
 
 
@@ -316,7 +456,7 @@ mod tests {
 
         let mut split = input.split("\n\n");
         let rules:Vec<Rule> = split.nth(0).unwrap().lines().map(|line| Rule::parse(line)).collect();
-        let rule_engine = RuleEngine { rules };
+        let rule_engine = RuleEngine::new(rules);
         let manual_updates:Vec<ManualUpdate> = split.nth(0).unwrap().lines().map(|line| ManualUpdate::parse(line)).collect();
 
 
@@ -325,11 +465,13 @@ mod tests {
         let mut updated_list:Vec<ManualUpdate> = Vec::new();
 
         for update in passed {
-            updated_list.push(update.reorder_pages(&rule_engine.rules));
+            match rule_engine.reorder(&update) {
+                Reordering::Unique(ordered) => updated_list.push(ordered),
+                other => panic!("{:?} should have one order", other)
+            }
         }
 
         let total = updated_list.iter().map(|m| m.get_middle_number()).sum::<i32>();
-        updated_list.iter().for_each(|f| f.print());
 
         assert_eq!(total, 123);
     }
@@ -361,7 +503,7 @@ mod tests {
 97,13,75,29,47"#;
         let mut split = input.split("\n\n");
         let rules: Vec<Rule> = split.nth(0).unwrap().lines().map(|line| Rule::parse(line)).collect();
-        let rule_engine = RuleEngine { rules };
+        let rule_engine = RuleEngine::new(rules);
         let manual_updates: Vec<ManualUpdate> = split.nth(0).unwrap().lines().map(|line| ManualUpdate::parse(line)).collect();
 
 
@@ -370,12 +512,101 @@ mod tests {
         let mut updated_list: Vec<ManualUpdate> = Vec::new();
 
         for update in passed {
-            updated_list.push(update.reorder_pages(&rule_engine.rules));
+            match rule_engine.reorder(&update) {
+                Reordering::Unique(ordered) => updated_list.push(ordered),
+                other => panic!("{:?} should have one order", other)
+            }
         }
 
         let total = updated_list.iter().map(|m| m.get_middle_number()).sum::<i32>();
-        updated_list.iter().for_each(|f| f.print());
 
         assert_eq!(total, 47);
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn cycles_are_reported_with_their_pages() {
+        let rules: Vec<Rule> = ["1|2", "2|3", "3|1", "4|1"].iter().map(|line| Rule::parse(line)).collect();
+        let rule_engine = RuleEngine::new(rules);
+
+        let reordering = rule_engine.reorder(&ManualUpdate::parse("4,3,2,1"));
+        assert_eq!(reordering, Reordering::Cycle(vec![1, 2, 3]));
+        assert_eq!(describe_cycle(&vec![1, 2, 3]), "pages 1 -> 2 -> 3 -> 1 form a cycle");
+        assert_eq!(rule_engine.reorder(&ManualUpdate::parse("2,1")), Reordering::Unique(ManualUpdate::parse("1,2")));
+    }
+
+    #[test]
+    fn loosely_constrained_updates_are_ambiguous() {
+        let rules: Vec<Rule> = ["1|2", "1|3"].iter().map(|line| Rule::parse(line)).collect();
+        let rule_engine = RuleEngine::new(rules);
+
+        assert_eq!(rule_engine.reorder(&ManualUpdate::parse("3,2,1")), Reordering::Ambiguous(ManualUpdate::parse("1,3,2")));
+    }
+
+    #[test]
+    fn violated_rules_are_reported() {
+        let input = r#"47|53
+97|13
+97|61
+97|47
+75|29
+61|13
+75|53
+29|13
+97|29
+53|29
+61|53
+97|53
+61|29
+47|13
+75|47
+97|75
+47|61
+75|61
+47|29
+75|13
+53|13
+
+75,47,61,53,29
+97,13,75,29,47"#;
+        let queue = parse(input.to_string());
+        let violations: Vec<String> = queue.rule_engine.violations(&queue.manual_updates[1]).iter().map(|r| r.to_string()).collect();
+
+        assert!(queue.rule_engine.violations(&queue.manual_updates[0]).is_empty());
+        assert_eq!(violations, vec!["29|13", "47|13", "47|29", "75|13"]);
+        assert_eq!(explain(&queue).lines().nth(1), Some("97,13,75,29,47: breaks 29|13 47|13 47|29 75|13, reordered to 97,75,47,29,13"));
+    }
+
+    #[test]
+    fn comparator_sorts_fully_constrained_updates() {
+        let rules: Vec<Rule> = ["1|2", "2|3", "1|3"].iter().map(|line| Rule::parse(line)).collect();
+        let rule_engine = RuleEngine::new(rules);
+
+        let mut pages = vec![3, 1, 2];
+        pages.sort_by(|a, b| rule_engine.compare(*a, *b));
+        assert_eq!(pages, vec![1, 2, 3]);
+        assert_eq!(rule_engine.compare(3, 1), Ordering::Greater);
+        assert_eq!(rule_engine.sort(&ManualUpdate::parse("2,3,1")), Reordering::Unique(ManualUpdate::parse("1,2,3")));
+    }
+
+    #[test]
+    fn intransitive_rules_fall_back_to_topological_order() {
+        let rules: Vec<Rule> = ["1|2", "2|3"].iter().map(|line| Rule::parse(line)).collect();
+        let rule_engine = RuleEngine::new(rules);
+
+        assert!(!rule_engine.is_total_order(&vec![1, 2, 3]));
+        assert_eq!(rule_engine.sort(&ManualUpdate::parse("3,1,2")), Reordering::Unique(ManualUpdate::parse("1,2,3")));
+    }
+
+    #[test]
+    fn parsed_queues_expose_their_comparator() {
+        let queue = parse("1|2\n2|3\n1|3\n\n3,1,2\n2,1".to_string());
+        let rule_engine = queue.rule_engine();
+
+        let sorted: Vec<Vec<i32>> = queue.manual_updates().iter().map(|update| {
+            let mut pages = update.pages().clone();
+            pages.sort_by(|a, b| rule_engine.compare(*a, *b));
+            pages
+        }).collect();
+        assert_eq!(sorted, vec![vec![1, 2, 3], vec![1, 2]]);
+    }
+}
@@ -3,36 +3,75 @@ use std::collections::{HashMap, HashSet};
 use std::fmt;
 use std::fmt::Display;
 use std::hash::Hash;
+use std::str::FromStr;
 use crate::domain::point::{Point, NORTH, SOUTH, EAST, WEST};
 
-pub fn part_one(input: String) -> impl Display {
-    let mut grid = Grid::parse(input);
-    let guard = Guard { position: grid.current_guard_position, direction: NORTH };
-    let traveled = walk_the_grid(&grid, guard);
+pub fn parse(input: String) -> Grid {
+    Grid::parse(input)
+}
+
+pub fn part_one(grid: &Grid) -> impl Display {
+    let traveled = walk_all_guards(grid, TurnPolicy::Right);
     grid.print_traveled(traveled.clone());
 
     traveled.len()
 }
-pub fn part_two(input: String) -> impl Display {
-    let mut grid = Grid::parse(input.to_string());
-    let guard = Guard { position: grid.current_guard_position, direction: NORTH };
-    let traveled = walk_the_grid(&grid, guard.clone());
+pub fn part_two(grid: &Grid) -> impl Display {
+    let traveled = walk_all_guards(grid, TurnPolicy::Right);
 
-    let obstacles = brute_force_put_obstacles(guard.clone(), grid, traveled);
+    let obstacles = find_loop_obstacles(grid, &grid.guards, traveled, TurnPolicy::Right);
     obstacles.len()
 }
 
-fn walk_the_grid(grid: &Grid, guard: Guard) -> HashSet<Point> {
+// What a guard does on meeting an obstacle. The puzzle's guards always turn right.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TurnPolicy {
+    Right,
+    Left,
+    Reverse
+}
+
+impl TurnPolicy {
+    fn turn(&self, direction: Point) -> Point {
+        match self {
+            TurnPolicy::Right => Point::new(direction.y, -direction.x),
+            TurnPolicy::Left => Point::new(-direction.y, direction.x),
+            TurnPolicy::Reverse => direction.scale(-1)
+        }
+    }
+}
+
+impl FromStr for TurnPolicy {
+    type Err = String;
+
+    fn from_str(input: &str) -> Result<Self, Self::Err> {
+        match input {
+            "right" => Ok(TurnPolicy::Right),
+            "left" => Ok(TurnPolicy::Left),
+            "reverse" => Ok(TurnPolicy::Reverse),
+            _ => Err(format!("unknown turning policy {}, expected right, left or reverse", input))
+        }
+    }
+}
+
+// Guards walk independently of each other; the result is every cell any of them visits.
+fn walk_all_guards(grid: &Grid, policy: TurnPolicy) -> HashSet<Point> {
+    grid.guards.iter().flat_map(|guard| walk_the_grid(grid, guard.clone(), policy)).collect()
+}
+
+fn walk_the_grid(grid: &Grid, guard: Guard, policy: TurnPolicy) -> HashSet<Point> {
 
     // check next position
     // If obstacle, turn 90 degrees
     // If not, move forward.
     let mut guard_check = guard.clone();
     let mut traveled:HashSet<Point> = HashSet::new();
+    let mut states:HashSet<Guard> = HashSet::new();
 
     traveled.insert(guard_check.position);
 
-    loop {
+    // A map can trap the guard in a loop without any added obstacle, so stop once a state repeats
+    while states.insert(guard_check.clone()) {
         let next_point = guard_check.position + guard_check.direction;
         if !grid.grid.contains_key(&next_point) {
             break;
@@ -41,7 +80,7 @@ fn walk_the_grid(grid: &Grid, guard: Guard) -> HashSet<Point> {
         match current_entity {
             Entity::Obstacle => {
                 // Rotate direction
-                guard_check = Guard {direction: get_rotation(guard_check.direction), position: guard_check.position };
+                guard_check = Guard {direction: policy.turn(guard_check.direction), position: guard_check.position };
             }
             Entity::Empty => {
                 // Move forward
@@ -61,82 +100,209 @@ fn walk_the_grid(grid: &Grid, guard: Guard) -> HashSet<Point> {
 }
 
 
-fn check_if_looped(grid: &Grid, guard: Guard, max_iterations: i32) -> bool {
+// For every empty cell and direction, where walking straight ends: the cell in front of the next obstacle,
+// or None when the guard walks off the map. Lets the guard move obstacle to obstacle instead of cell by cell.
+struct JumpTable {
+    stops: HashMap<(Point, Point), Option<Point>>
+}
 
-    // check next position
-    // If obstacle, turn 90 degrees
-    // If not, move forward.
-    let mut current_iterations = 0;
-    let mut guard_check = guard.clone();
-    let mut traveled:HashSet<Point> = HashSet::new();
+impl JumpTable {
+    fn new(grid: &Grid) -> Self {
+        let mut stops: HashMap<(Point, Point), Option<Point>> = HashMap::new();
+
+        for direction in [NORTH, EAST, SOUTH, WEST] {
+            // Walk each line backwards from the edge the guard would leave by
+            let edges = grid.grid.keys().filter(|point| !grid.grid.contains_key(&(**point + direction)));
+            for edge in edges {
+                let mut stop = None;
+                let mut current = *edge;
+                while let Some(entity) = grid.grid.get(&current) {
+                    match entity {
+                        Entity::Obstacle => stop = Some(current - direction),
+                        Entity::Empty => {
+                            stops.insert((current, direction), stop);
+                        }
+                    }
+                    current = current - direction;
+                }
+            }
+        }
 
-    traveled.insert(guard_check.position);
+        JumpTable { stops }
+    }
+
+    fn stop(&self, position: Point, direction: Point) -> Option<Point> {
+        self.stops[&(position, direction)]
+    }
+}
+
+// How many steps along `direction` it takes to get from `from` to `to`, if `to` is straight ahead.
+fn steps_ahead(from: Point, to: Point, direction: Point) -> Option<i32> {
+    let delta = to - from;
+    let steps = delta.x * direction.x + delta.y * direction.y;
+    (steps > 0 && delta == direction.scale(steps)).then_some(steps)
+}
+
+// Jumps between obstacles with `extra_obstacle` added to the map, remembering every (position, direction) the
+// guard turns at. Seeing one again means the guard is walking the same loop.
+fn check_if_looped(jumps: &JumpTable, guard: Guard, extra_obstacle: Point, policy: TurnPolicy) -> bool {
+    let mut turns: HashSet<Guard> = HashSet::new();
+    let mut guard = guard;
 
     loop {
-        let next_point = guard_check.position + guard_check.direction;
-        if !grid.grid.contains_key(&next_point) {
-            return false;
+        let stop = jumps.stop(guard.position, guard.direction);
+        let blocked_at = steps_ahead(guard.position, extra_obstacle, guard.direction)
+            .filter(|steps| stop.map_or(true, |stop| *steps <= steps_ahead(guard.position, stop, guard.direction).unwrap_or(0) + 1));
+
+        guard.position = match (blocked_at, stop) {
+            (Some(_), _) => extra_obstacle - guard.direction,
+            (None, Some(stop)) => stop,
+            (None, None) => return false
+        };
+        guard.direction = policy.turn(guard.direction);
+
+        if !turns.insert(guard.clone()) {
+            return true;
         }
-        let current_entity = grid.grid.get(&next_point).unwrap();
-        match current_entity {
-            Entity::Obstacle => {
-                // Rotate direction
-                guard_check = Guard {direction: get_rotation(guard_check.direction), position: guard_check.position };
+    }
+}
+
+// Tries an obstacle on each cell of the original paths except the guards' starting cells. An obstacle
+// counts if it traps any of the guards.
+fn find_loop_obstacles(grid: &Grid, guards: &Vec<Guard>, traveled: HashSet<Point>, policy: TurnPolicy) -> Vec<Point> {
+    let jumps = JumpTable::new(grid);
+
+    traveled.into_iter()
+        .filter(|point| guards.iter().all(|guard| guard.position != *point))
+        .filter(|point| guards.iter().any(|guard| check_if_looped(&jumps, guard.clone(), *point, policy)))
+        .collect()
+}
+
+fn guard_glyph(direction: Point) -> char {
+    match direction {
+        NORTH => '^',
+        EAST => '>',
+        SOUTH => 'v',
+        _ => '<'
+    }
+}
+
+// Builds a frame by frame replay of one guard's walk. `obstacle` is an extra obstacle at (column, row),
+// counted from the top left as the map is printed.
+pub fn replay(grid: &Grid, guard: usize, obstacle: Option<(i32, i32)>, policy: TurnPolicy) -> Result<Replay, String> {
+    let start = grid.guards.get(guard).ok_or(format!("the map has {} guard(s), no guard {}", grid.guards.len(), guard))?.clone();
+    let obstacle = match obstacle {
+        None => None,
+        Some((column, row)) => {
+            let point = Point::new(column, grid.total_size.y - 1 - row);
+            if !grid.grid.contains_key(&point) {
+                return Err(format!("column {}, row {} is off the map", column, row));
             }
-            Entity::Empty => {
-                // Move forward
-                traveled.insert(next_point);
-                guard_check = Guard {direction: guard_check.direction, position: next_point };
+            if grid.guards.iter().any(|guard| guard.position == point) {
+                return Err("an obstacle can't go on a guard's starting cell".to_string());
             }
+            Some(point)
         }
+    };
+    let blocked = |point: &Point| Some(*point) == obstacle || grid.grid.get(point) == Some(&Entity::Obstacle);
 
-        if(guard_check.position.x < 0 || guard_check.position.y < 0 || guard_check.position.x >= grid.total_size.x || guard_check.position.y >= grid.total_size.y)
-        {
-            return false;
+    let mut steps = vec![Step { guard: start.clone(), turned: false }];
+    let mut seen: HashMap<Guard, usize> = HashMap::from([(start, 0)]);
+    let mut loop_start = None;
+    loop {
+        let guard = steps.last().unwrap().guard.clone();
+        let next_point = guard.position + guard.direction;
+        if !grid.grid.contains_key(&next_point) {
+            break;
         }
-        current_iterations += 1;
 
-        if(current_iterations > max_iterations) {
-            return true;
+        let step = if blocked(&next_point) {
+            Step { guard: Guard { position: guard.position, direction: policy.turn(guard.direction) }, turned: true }
+        } else {
+            Step { guard: Guard { position: next_point, direction: guard.direction }, turned: false }
+        };
+        if let Some(index) = seen.get(&step.guard) {
+            loop_start = Some(*index);
+            break;
         }
+        seen.insert(step.guard.clone(), steps.len());
+        steps.push(step);
     }
 
+    Ok(Replay { grid: grid.clone(), obstacle, steps, loop_start })
+}
 
-    return false;
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Step {
+    guard: Guard,
+    // Whether the guard got here by turning on the spot rather than stepping forward
+    turned: bool
 }
 
+pub struct Replay {
+    grid: Grid,
+    obstacle: Option<Point>,
+    steps: Vec<Step>,
+    // The step the walk returns to when the guard is stuck in a loop
+    loop_start: Option<usize>
+}
 
+const GUARD_COLOUR: &str = "#FFD600";
+const PATH_COLOUR: &str = "#40C4FF";
+const LOOP_COLOUR: &str = "#E040FB";
+const OBSTACLE_COLOUR: &str = "#FF5252";
 
-fn brute_force_put_obstacles(guard: Guard, mut grid: Grid, traveled: HashSet<Point>) -> Vec<Point> {
-    let mut obstacle_positions:Vec<Point> = Vec::new();
-    for point in traveled {
-        // Do Inner Loop Check
-        let mut updated_grid = grid.clone();
-        updated_grid.grid.insert(point, Entity::Obstacle);
-        if check_if_looped(&updated_grid, guard.clone(), 10000) {
-            obstacle_positions.push(point);
-        }
+impl Replay {
+    pub fn len(&self) -> usize {
+        self.steps.len()
     }
 
-    obstacle_positions
+    // The map after `step` moves: the guard as an arrow, earlier steps as the arrow they were walked in,
+    // '+' where the guard turned, and the loop (if any) in its own colour.
+    pub fn frame(&self, step: usize) -> String {
+        let step = step.min(self.steps.len() - 1);
+        let current = &self.steps[step].guard;
+
+        let mut trail: HashMap<Point, char> = HashMap::new();
+        for earlier in &self.steps[..step] {
+            let glyph = if earlier.turned { '+' } else { guard_glyph(earlier.guard.direction) };
+            if trail.get(&earlier.guard.position) != Some(&'+') {
+                trail.insert(earlier.guard.position, glyph);
+            }
+        }
+        let loop_cells: HashSet<Point> = self.loop_start
+            .map(|start| self.steps[start..].iter().map(|s| s.guard.position).collect())
+            .unwrap_or_default();
+
+        let mut lines: Vec<String> = Vec::new();
+        for y in (0..self.grid.total_size.y).rev() {
+            let mut line = String::new();
+            for x in 0..self.grid.total_size.x {
+                let point = Point::new(x, y);
+                let cell = if point == current.position {
+                    ansi_hex_color::colored(GUARD_COLOUR, "", &guard_glyph(current.direction).to_string())
+                } else if Some(point) == self.obstacle {
+                    ansi_hex_color::colored(OBSTACLE_COLOUR, "", "O")
+                } else if let Some(glyph) = trail.get(&point) {
+                    let colour = if loop_cells.contains(&point) { LOOP_COLOUR } else { PATH_COLOUR };
+                    ansi_hex_color::colored(colour, "", &glyph.to_string())
+                } else {
+                    self.grid.grid.get(&point).unwrap().to_string()
+                };
+                line.push_str(&cell);
+            }
+            lines.push(line);
+        }
 
-}
+        let ending = match self.loop_start {
+            Some(start) => format!("loops back to step {} every {} steps", start, self.steps.len() - start),
+            None => format!("walks off the map after step {}", self.steps.len() - 1)
+        };
+        lines.push(format!("step {}/{}: facing {} at column {}, row {}, {}", step, self.steps.len() - 1,
+            guard_glyph(current.direction), current.position.x, self.grid.total_size.y - 1 - current.position.y, ending));
 
-fn get_rotation(direction: Point) -> Point {
-    if(direction == NORTH) {
-        return EAST
-    }
-    if(direction == EAST) {
-        return SOUTH;
-    }
-    if(direction == SOUTH) {
-        return WEST;
-    }
-    if(direction == WEST) {
-        return NORTH
+        lines.join("\n")
     }
-
-    return direction
 }
 
 #[derive(Copy, Clone, PartialEq, Eq)]
@@ -161,10 +327,10 @@ struct Guard {
 }
 
 #[derive(PartialEq, Eq, Clone)]
-struct Grid {
+pub struct Grid {
     grid: HashMap<Point, Entity>,
     total_size: Point,
-    current_guard_position: Point
+    guards: Vec<Guard>
 }
 
 impl Grid {
@@ -172,7 +338,7 @@ impl Grid {
         let mut y_index = (input.lines().count() as i32);
         let mut map:HashMap<Point, Entity> = HashMap::new();
         let mut total_size:Point = Point::parse(input.lines().nth(0).unwrap().chars().count(), y_index as usize);
-        let mut current_guard_position = Point::new(0,0);
+        let mut guards: Vec<Guard> = Vec::new();
         y_index -= 1;
         for (y, line) in input.lines().enumerate() {
             for (x, node) in line.chars().enumerate() {
@@ -181,8 +347,15 @@ impl Grid {
                     _ => Entity::Empty
                 };
 
-                if(node == '^') {
-                    current_guard_position = Point::new(x as i32, y_index as i32);
+                let direction = match node {
+                    '^' => Some(NORTH),
+                    '>' => Some(EAST),
+                    'v' => Some(SOUTH),
+                    '<' => Some(WEST),
+                    _ => None
+                };
+                if let Some(direction) = direction {
+                    guards.push(Guard { position: Point::new(x as i32, y_index as i32), direction });
                 }
 
                 map.insert(Point::new(x as i32, y_index as i32), entity);
@@ -191,7 +364,7 @@ impl Grid {
 
         }
 
-        Grid { grid: map, total_size, current_guard_position }
+        Grid { grid: map, total_size, guards }
     }
 
     fn print(&self) {
@@ -222,8 +395,27 @@ impl Grid {
 
 #[cfg(test)]
 mod tests {
-    use crate::domain::point::NORTH;
-    use super::{brute_force_put_obstacles, walk_the_grid, Grid, Guard};
+    use std::collections::HashSet;
+    use crate::domain::point::{Point, EAST, NORTH, SOUTH, WEST};
+    use crate::{generators, property};
+    use super::{check_if_looped, find_loop_obstacles, part_one, replay, walk_the_grid, Entity, Grid, Guard, JumpTable, TurnPolicy};
+
+    // Reference loop check: the guard loops exactly when it repeats a position and direction.
+    fn walks_into_loop(grid: &Grid, guard: Guard) -> bool {
+        let mut seen:HashSet<Guard> = HashSet::new();
+        let mut guard = guard;
+        loop {
+            if !seen.insert(guard.clone()) {
+                return true;
+            }
+            let next_point = guard.position + guard.direction;
+            match grid.grid.get(&next_point) {
+                None => return false,
+                Some(Entity::Obstacle) => guard.direction = TurnPolicy::Right.turn(guard.direction),
+                Some(Entity::Empty) => guard.position = next_point
+            }
+        }
+    }
 
     #[test]
     fn can_track_guard_positions(){
@@ -238,8 +430,8 @@ mod tests {
 #.........
 ......#..."#;
         let mut grid = Grid::parse(input.to_string());
-        let guard = Guard { position: grid.current_guard_position, direction: NORTH };
-        let traveled = walk_the_grid(&grid, guard);
+        let guard = grid.guards[0].clone();
+        let traveled = walk_the_grid(&grid, guard, TurnPolicy::Right);
 
         grid.print_traveled(traveled.clone());
 
@@ -259,12 +451,80 @@ mod tests {
 #.........
 ......#..."#;
         let mut grid = Grid::parse(input.to_string());
-        let guard = Guard { position: grid.current_guard_position, direction: NORTH };
-        let traveled = walk_the_grid(&grid, guard.clone());
+        let traveled = walk_the_grid(&grid, grid.guards[0].clone(), TurnPolicy::Right);
 
         grid.print_traveled(traveled.clone());
-        let obstacles = brute_force_put_obstacles(guard.clone(), grid, traveled);
+        let obstacles = find_loop_obstacles(&grid, &grid.guards, traveled, TurnPolicy::Right);
 
         assert_eq!(obstacles.len(), 6);
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn replay_follows_the_guard_and_finds_loops() {
+        let input = r#"....#.....
+.........#
+..........
+..#.......
+.......#..
+..........
+.#..^.....
+........#.
+#.........
+......#..."#;
+        let grid = Grid::parse(input.to_string());
+
+        let walk = replay(&grid, 0, None, TurnPolicy::Right).unwrap();
+        assert_eq!(walk.loop_start, None);
+        assert_eq!(walk.frame(0).lines().last(), Some("step 0/54: facing ^ at column 4, row 6, walks off the map after step 54"));
+        assert!(walk.frame(7).lines().nth(1).unwrap().contains(&ansi_hex_color::colored("#40C4FF", "", "+")));
+
+        let looped = replay(&grid, 0, Some((3, 6)), TurnPolicy::Right).unwrap();
+        assert!(looped.loop_start.is_some());
+        assert!(looped.frame(1000).contains(&ansi_hex_color::colored("#FF5252", "", "O")));
+        assert!(replay(&grid, 0, Some((4, 6)), TurnPolicy::Right).is_err());
+    }
+
+    #[test]
+    fn jump_table_loop_check_matches_cell_by_cell_walk() {
+        property::check(200, |rng| {
+            let width = rng.range(3, 9);
+            let height = rng.range(3, 9);
+            generators::guard_map(rng, width, height, 15)
+        }, |input| {
+            let grid = Grid::parse(input.to_string());
+            let guard = grid.guards[0].clone();
+            let jumps = JumpTable::new(&grid);
+
+            for point in walk_the_grid(&grid, guard.clone(), TurnPolicy::Right).into_iter().filter(|point| *point != guard.position) {
+                let mut updated_grid = grid.clone();
+                updated_grid.grid.insert(point, Entity::Obstacle);
+                property::assert_same(&format!("obstacle at {}", point), walks_into_loop(&updated_grid, guard.clone()), check_if_looped(&jumps, guard.clone(), point, TurnPolicy::Right))?;
+            }
+            Ok(())
+        });
+    }
+
+    #[test]
+    fn every_guard_glyph_is_recognised() {
+        let grid = Grid::parse("..#.\n>..<\n.v..".to_string());
+
+        assert_eq!(grid.guards, vec![
+            Guard { position: Point::new(0, 1), direction: EAST },
+            Guard { position: Point::new(3, 1), direction: WEST },
+            Guard { position: Point::new(1, 0), direction: SOUTH }
+        ]);
+        assert_eq!(part_one(&grid).to_string(), "5");
+    }
+
+    #[test]
+    fn turning_policies_change_the_walk() {
+        let grid = Grid::parse("...\n.#.\n...\n.^.".to_string());
+        let guard = grid.guards[0].clone();
+
+        assert_eq!(TurnPolicy::Left.turn(NORTH), WEST);
+        assert_eq!(TurnPolicy::Right.turn(NORTH), EAST);
+        assert_eq!(walk_the_grid(&grid, guard.clone(), TurnPolicy::Right), [Point::new(1, 0), Point::new(1, 1), Point::new(2, 1)].into());
+        assert_eq!(walk_the_grid(&grid, guard.clone(), TurnPolicy::Left), [Point::new(1, 0), Point::new(1, 1), Point::new(0, 1)].into());
+        assert_eq!(walk_the_grid(&grid, guard, TurnPolicy::Reverse), [Point::new(1, 0), Point::new(1, 1)].into());
+    }
+}
@@ -1,118 +1,347 @@
-﻿use std::fmt::Display;
-use std::ptr::eq;
-use nom::bytes::streaming::take_until;
-use nom::combinator::value;
-use crate::tools::{parse_numbers_i128, parse_numbers_i64};
+﻿use std::collections::HashMap;
+use std::fmt;
+use std::fmt::Display;
+use crate::tools::parse_numbers_i128;
 
-pub fn part_one(input: String) -> impl Display {
-    let equations:Vec<Equation> = input.lines().map(|line| Equation::parse(line)).filter(|x| attempt_solve(x.clone())).collect();
-
-    equations.iter().map(|x| x.result).sum::<i128>()
+pub fn parse(input: String) -> Vec<Equation> {
+    input.lines().map(|line| Equation::parse(line)).collect()
 }
 
-pub fn part_two(input: String) -> impl Display {
-    let equations:Vec<Equation> = input.lines().map(|line| Equation::parse(line)).filter(|x| attempt_solve_part_2(x.clone())).collect();
+pub fn part_one(equations: &Vec<Equation>) -> impl Display {
+    equations.iter().filter(|x| attempt_solve((*x).clone())).map(|x| x.result).sum::<i128>()
+}
 
-    equations.iter().map(|x| x.result).sum::<i128>()
+pub fn part_two(equations: &Vec<Equation>) -> impl Display {
+    equations.iter().filter(|x| attempt_solve_part_2((*x).clone())).map(|x| x.result).sum::<i128>()
 }
 
 fn attempt_solve_part_2(equation: Equation) -> bool {
+    solvable(equation.result, &equation.values, &PART_TWO_OPERATORS)
+}
 
-    let combos = generate_combinations_part_2(equation.values.len());
+fn attempt_solve(equation: Equation) -> bool {
+    solvable(equation.result, &equation.values, &PART_ONE_OPERATORS)
+}
 
-    attempt_to_solve_dynamically(equation, combos.clone())
+// Sums the results of the equations that some assignment of `operators` makes true.
+pub fn calibrate(equations: &Vec<Equation>, operators: &[&dyn Operator]) -> i128 {
+    equations.iter().filter(|x| solvable(x.result, &x.values, operators)).map(|x| x.result).sum::<i128>()
 }
 
-fn attempt_solve(equation: Equation) -> bool {
-    let combos = generate_combinations(equation.values.len());
-
-    attempt_to_solve_dynamically(equation, combos.clone())
-}
-
-fn attempt_to_solve_dynamically(equation: Equation, combos: Vec<Vec<char>>) -> bool {
-    for combo in combos {
-        let mut result = *equation.values.iter().nth(0).unwrap();
-        for x in 1..equation.values.len() {
-            let next = *equation.values.iter().nth(x).unwrap();
-
-            let operator = *combo.iter().nth(x - 1).unwrap();
-            match operator
-            {
-                '*' => {
-                    result = result * next;
-                },
-                '+' => {
-                    result = result + next;
-                },
-                '|' => {
-                    result = parse_numbers_i128(&(result.to_string() + &next.to_string())).unwrap().1;
-                }
-                _ => {}
-            }
+// One operator assignment that makes an equation true, printed like `81 + 40 * 27 = 3267`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Witness {
+    result: i128,
+    values: Vec<i128>,
+    symbols: Vec<&'static str>
+}
+
+impl fmt::Display for Witness {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.values[0])?;
+        for (symbol, value) in self.symbols.iter().zip(&self.values[1..]) {
+            write!(f, " {} {}", symbol, value)?;
         }
+        write!(f, " = {}", self.result)
+    }
+}
+
+pub fn witnesses(equation: &Equation, operators: &[&dyn Operator]) -> Vec<Witness> {
+    let non_negative = is_non_negative(&equation.values, operators);
+    witnesses_backward(equation.result, &equation.values, operators, non_negative).into_iter()
+        .map(|symbols| Witness { result: equation.result, values: equation.values.clone(), symbols })
+        .collect()
+}
+
+// Counts the satisfying assignments without building them. Branches that reach the same target with the
+// same operands left are only counted once, so equations with huge numbers of solutions stay cheap.
+pub fn count_witnesses(equation: &Equation, operators: &[&dyn Operator]) -> u128 {
+    let non_negative = is_non_negative(&equation.values, operators);
+    count_backward(equation.result, &equation.values, operators, non_negative, &mut HashMap::new())
+}
+
+// An operator placed between two operands, always evaluated left to right.
+pub trait Operator {
+    fn symbol(&self) -> &'static str;
+
+    fn apply(&self, left: i128, right: i128) -> Option<i128>;
+
+    // Operators without an inverse are solved by evaluating the operands before them forwards, which is slow.
+    fn undo(&self, _target: i128, _right: i128) -> Undo {
+        Undo::Unknown
+    }
+
+    // Whether non-negative operands always give a non-negative result, which lets the solver drop negative targets.
+    fn keeps_non_negative(&self) -> bool {
+        true
+    }
+}
+
+// What undoing an operator says about the value to its left.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Undo {
+    Left(i128),
+    Impossible,
+    // Either the operator has no inverse or any left value would do, like multiplying by zero
+    Unknown
+}
+
+struct Add;
+struct Multiply;
+struct Concatenate;
+struct Subtract;
+struct Xor;
+struct Power;
+
+impl Operator for Add {
+    fn symbol(&self) -> &'static str { "+" }
+
+    fn apply(&self, left: i128, right: i128) -> Option<i128> {
+        left.checked_add(right)
+    }
+
+    fn undo(&self, target: i128, right: i128) -> Undo {
+        target.checked_sub(right).map_or(Undo::Impossible, Undo::Left)
+    }
+}
+
+impl Operator for Multiply {
+    fn symbol(&self) -> &'static str { "*" }
+
+    fn apply(&self, left: i128, right: i128) -> Option<i128> {
+        left.checked_mul(right)
+    }
 
-        if(result == equation.result){
-            return true;
+    fn undo(&self, target: i128, right: i128) -> Undo {
+        match right {
+            0 => Undo::Unknown,
+            _ if target % right == 0 => Undo::Left(target / right),
+            _ => Undo::Impossible
         }
-        //println!("{}", result);
     }
-    false
 }
 
-fn generate_combinations(length: usize) -> Vec<Vec<char>> {
-    let mut combinations = Vec::new();
-    let total_combinations = 1 << length; // 2^length
+// Only defined for non-negative operands, where it appends the digits of the right to the left.
+impl Operator for Concatenate {
+    fn symbol(&self) -> &'static str { "||" }
 
-    for i in 0..total_combinations {
-        let mut combination = Vec::new();
+    fn apply(&self, left: i128, right: i128) -> Option<i128> {
+        if left < 0 || right < 0 {
+            return None;
+        }
+        left.checked_mul(10i128.checked_pow(digits(right))?)?.checked_add(right)
+    }
 
-        for j in 0..length {
-            if (i & (1 << j)) != 0 {
-                combination.push('+');
-            } else {
-                combination.push('*');
-            }
+    fn undo(&self, target: i128, right: i128) -> Undo {
+        if target < 0 || right < 0 {
+            return Undo::Impossible;
         }
+        match 10i128.checked_pow(digits(right)) {
+            Some(shift) if target % shift == right => Undo::Left(target / shift),
+            _ => Undo::Impossible
+        }
+    }
+}
+
+impl Operator for Subtract {
+    fn symbol(&self) -> &'static str { "-" }
 
-        combinations.push(combination);
+    fn apply(&self, left: i128, right: i128) -> Option<i128> {
+        left.checked_sub(right)
     }
 
-    combinations
+    fn undo(&self, target: i128, right: i128) -> Undo {
+        target.checked_add(right).map_or(Undo::Impossible, Undo::Left)
+    }
+
+    fn keeps_non_negative(&self) -> bool {
+        false
+    }
 }
 
+impl Operator for Xor {
+    fn symbol(&self) -> &'static str { "^" }
 
-fn generate_combinations_part_2(length: usize) -> Vec<Vec<char>> {
-    let mut combinations = Vec::new();
-    let total_combinations = 3_usize.pow(length as u32);
+    fn apply(&self, left: i128, right: i128) -> Option<i128> {
+        Some(left ^ right)
+    }
 
-    for i in 0..total_combinations {
-        let mut combination = Vec::new();
-        let mut current = i;
+    fn undo(&self, target: i128, right: i128) -> Undo {
+        Undo::Left(target ^ right)
+    }
+}
 
-        for _ in 0..length {
-            match current % 3 {
-                0 => combination.push('*'),
-                1 => combination.push('+'),
-                2 => combination.push('|'),
-                _ => unreachable!(),
-            }
-            current /= 3;
+impl Operator for Power {
+    fn symbol(&self) -> &'static str { "**" }
+
+    fn apply(&self, left: i128, right: i128) -> Option<i128> {
+        left.checked_pow(u32::try_from(right).ok()?)
+    }
+}
+
+fn digits(value: i128) -> u32 {
+    value.checked_ilog10().unwrap_or(0) + 1
+}
+
+const PART_ONE_OPERATORS: [&dyn Operator; 2] = [&Add, &Multiply];
+const PART_TWO_OPERATORS: [&dyn Operator; 3] = [&Add, &Multiply, &Concatenate];
+
+const OPERATORS: [(&str, &dyn Operator); 6] = [
+    ("add", &Add),
+    ("multiply", &Multiply),
+    ("concatenate", &Concatenate),
+    ("subtract", &Subtract),
+    ("xor", &Xor),
+    ("power", &Power)
+];
+
+// Looks up a comma separated list of operator names, e.g. "add,multiply,concatenate".
+pub fn parse_operators(names: &str) -> Result<Vec<&'static dyn Operator>, String> {
+    names.split(',').map(|name| {
+        OPERATORS.iter().find(|(known, _)| *known == name.trim()).map(|(_, operator)| *operator).ok_or_else(|| {
+            let known: Vec<&str> = OPERATORS.iter().map(|(known, _)| *known).collect();
+            format!("unknown operator {}, expected one of {}", name.trim(), known.join(", "))
+        })
+    }).collect()
+}
+
+fn solvable(target: i128, values: &[i128], operators: &[&dyn Operator]) -> bool {
+    solvable_backward(target, values, operators, is_non_negative(values, operators))
+}
+
+fn is_non_negative(values: &[i128], operators: &[&dyn Operator]) -> bool {
+    values.iter().all(|value| *value >= 0) && operators.iter().all(|operator| operator.keeps_non_negative())
+}
+
+// Works from the last operand back to the first, undoing each operator in turn. A branch dies as soon as
+// an operator can't be undone, like a target that isn't divisible by the operand, so most of the 3^n
+// assignments are never looked at.
+fn solvable_backward(target: i128, values: &[i128], operators: &[&dyn Operator], non_negative: bool) -> bool {
+    if non_negative && target < 0 {
+        return false;
+    }
+    let Some((last, rest)) = values.split_last() else { return false };
+    if rest.is_empty() {
+        return target == *last;
+    }
+
+    operators.iter().any(|operator| match operator.undo(target, *last) {
+        Undo::Left(left) => solvable_backward(left, rest, operators, non_negative),
+        Undo::Impossible => false,
+        Undo::Unknown => forward_values(rest, operators).iter().any(|left| operator.apply(*left, *last) == Some(target))
+    })
+}
+
+// The same walk as solvable_backward, keeping every branch that succeeds rather than stopping at the first.
+fn witnesses_backward(target: i128, values: &[i128], operators: &[&dyn Operator], non_negative: bool) -> Vec<Vec<&'static str>> {
+    if non_negative && target < 0 {
+        return Vec::new();
+    }
+    let Some((last, rest)) = values.split_last() else { return Vec::new() };
+    if rest.is_empty() {
+        return if target == *last { vec![Vec::new()] } else { Vec::new() };
+    }
+
+    let mut found: Vec<Vec<&'static str>> = Vec::new();
+    for operator in operators {
+        let prefixes = match operator.undo(target, *last) {
+            Undo::Left(left) => witnesses_backward(left, rest, operators, non_negative),
+            Undo::Impossible => Vec::new(),
+            Undo::Unknown => forward_assignments(rest, operators).into_iter()
+                .filter(|(left, _)| operator.apply(*left, *last) == Some(target))
+                .map(|(_, symbols)| symbols)
+                .collect()
+        };
+        for mut symbols in prefixes {
+            symbols.push(operator.symbol());
+            found.push(symbols);
         }
+    }
+
+    found
+}
+
+fn count_backward(target: i128, values: &[i128], operators: &[&dyn Operator], non_negative: bool, seen: &mut HashMap<(i128, usize), u128>) -> u128 {
+    if non_negative && target < 0 {
+        return 0;
+    }
+    let Some((last, rest)) = values.split_last() else { return 0 };
+    if rest.is_empty() {
+        return (target == *last) as u128;
+    }
+    if let Some(count) = seen.get(&(target, values.len())) {
+        return *count;
+    }
 
-        combinations.push(combination);
+    let mut count = 0;
+    for operator in operators {
+        count += match operator.undo(target, *last) {
+            Undo::Left(left) => count_backward(left, rest, operators, non_negative, seen),
+            Undo::Impossible => 0,
+            Undo::Unknown => forward_counts(rest, operators).iter()
+                .filter(|(left, _)| operator.apply(**left, *last) == Some(target))
+                .map(|(_, ways)| ways)
+                .sum()
+        };
     }
 
-    combinations
+    seen.insert((target, values.len()), count);
+    count
 }
 
+// Every value the operands can produce left to right along with the operators that produce it.
+fn forward_assignments(values: &[i128], operators: &[&dyn Operator]) -> Vec<(i128, Vec<&'static str>)> {
+    let Some((first, rest)) = values.split_first() else { return Vec::new() };
+
+    rest.iter().fold(vec![(*first, Vec::new())], |results, value| {
+        results.iter().flat_map(|(result, symbols)| operators.iter().filter_map(move |operator| {
+            let mut symbols = symbols.clone();
+            symbols.push(operator.symbol());
+            Some((operator.apply(*result, *value)?, symbols))
+        })).collect()
+    })
+}
+
+// How many assignments produce each value, merging assignments that meet at the same value.
+fn forward_counts(values: &[i128], operators: &[&dyn Operator]) -> HashMap<i128, u128> {
+    let Some((first, rest)) = values.split_first() else { return HashMap::new() };
+
+    rest.iter().fold(HashMap::from([(*first, 1)]), |results, value| {
+        let mut next: HashMap<i128, u128> = HashMap::new();
+        for (result, ways) in results {
+            for operator in operators {
+                if let Some(produced) = operator.apply(result, *value) {
+                    *next.entry(produced).or_insert(0) += ways;
+                }
+            }
+        }
+        next
+    })
+}
+
+// Every value the operands can produce left to right. Exponential, so only used where undoing can't help.
+fn forward_values(values: &[i128], operators: &[&dyn Operator]) -> Vec<i128> {
+    let Some((first, rest)) = values.split_first() else { return Vec::new() };
+
+    rest.iter().fold(vec![*first], |results, value| {
+        results.iter().flat_map(|result| operators.iter().filter_map(move |operator| operator.apply(*result, *value))).collect()
+    })
+}
 
 #[derive(Debug, PartialEq, Eq, Hash, Clone, PartialOrd, Ord)]
-struct Equation
+pub struct Equation
 {
     result: i128,
     values: Vec<i128>
 }
 
+impl fmt::Display for Equation {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let values: Vec<String> = self.values.iter().map(|value| value.to_string()).collect();
+        write!(f, "{}: {}", self.result, values.join(" "))
+    }
+}
+
 impl Equation
 {
     fn parse(input_line: &str) -> Self {
@@ -126,8 +355,8 @@ impl Equation
 
 #[cfg(test)]
 mod tests {
-    use std::ptr::eq;
-    use crate::days::day_07::{attempt_solve, attempt_solve_part_2, Equation};
+    use crate::{generators, property};
+    use crate::days::day_07::{attempt_solve, attempt_solve_part_2, calibrate, count_witnesses, forward_assignments, forward_values, parse, parse_operators, solvable, witnesses, Equation, OPERATORS, PART_ONE_OPERATORS, PART_TWO_OPERATORS};
 
     #[test]
     fn can_parse_equation() {
@@ -184,4 +413,100 @@ mod tests {
 
         assert_eq!(equations.iter().map(|x| x.result).sum::<i128>(), 11387);
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn long_equations_are_solved_without_enumerating() {
+        let values = vec![7; 60];
+        let equation = Equation { result: 7 * 60, values };
+
+        assert!(attempt_solve(equation.clone()));
+        assert!(!attempt_solve(Equation { result: 7 * 60 + 1, ..equation }));
+    }
+
+    #[test]
+    fn zero_operands_are_handled() {
+        assert!(solvable(0, &[5, 3, 0], &PART_ONE_OPERATORS));
+        assert!(solvable(50, &[5, 0], &PART_TWO_OPERATORS));
+        assert!(!solvable(1, &[5, 3, 0], &PART_ONE_OPERATORS));
+    }
+
+    #[test]
+    fn backward_solver_matches_forward_evaluation() {
+        property::check(100, |rng| generators::generate(7, rng, 6).unwrap(), |input| {
+            for equation in parse(input.to_string()) {
+                for operators in [&PART_ONE_OPERATORS[..], &PART_TWO_OPERATORS[..]] {
+                    let expected = forward_values(&equation.values, operators).contains(&equation.result);
+                    let symbols: Vec<&str> = operators.iter().map(|operator| operator.symbol()).collect();
+                    property::assert_same(&format!("{:?} with {:?}", equation, symbols), expected, solvable(equation.result, &equation.values, operators))?;
+                }
+            }
+            Ok(())
+        });
+    }
+
+    #[test]
+    fn operator_sets_can_be_chosen_by_name() {
+        let equations = parse("190: 10 19\n3267: 81 40 27\n7290: 6 8 6 15\n9: 12 3\n64: 2 3 2".to_string());
+
+        assert_eq!(calibrate(&equations, &parse_operators("add,multiply").unwrap()), 190 + 3267);
+        assert_eq!(calibrate(&equations, &parse_operators("add, multiply, concatenate").unwrap()), 190 + 3267 + 7290);
+        assert_eq!(calibrate(&equations, &parse_operators("subtract,power").unwrap()), 9 + 64);
+        assert_eq!(parse_operators("add,divide").err(), Some("unknown operator divide, expected one of add, multiply, concatenate, subtract, xor, power".to_string()));
+    }
+
+    #[test]
+    fn inverses_agree_with_forward_evaluation() {
+        property::check(100, |rng| {
+            let mut names: Vec<&str> = OPERATORS.iter().map(|(name, _)| *name).filter(|_| rng.chance(50)).collect();
+            if names.is_empty() {
+                names.push("add");
+            }
+            let values: Vec<String> = (0..rng.range(1, 6)).map(|_| rng.range(-5, 20).to_string()).collect();
+            format!("{}\n{}: {}", names.join(","), rng.range(-50, 400), values.join(" "))
+        }, |input| {
+            let (names, equation) = input.split_once('\n').unwrap();
+            let operators = parse_operators(names).unwrap();
+            let equation = Equation::parse(equation);
+
+            let expected = forward_values(&equation.values, &operators).contains(&equation.result);
+            property::assert_same("solvable", expected, solvable(equation.result, &equation.values, &operators))
+        });
+    }
+
+    #[test]
+    fn witnesses_print_as_expressions() {
+        let equation = Equation::parse("3267: 81 40 27");
+        let found: Vec<String> = witnesses(&equation, &PART_ONE_OPERATORS).iter().map(|w| w.to_string()).collect();
+
+        assert_eq!(found, vec!["81 * 40 + 27 = 3267", "81 + 40 * 27 = 3267"]);
+        assert_eq!(count_witnesses(&equation, &PART_ONE_OPERATORS), 2);
+        assert!(witnesses(&Equation::parse("83: 17 5"), &PART_TWO_OPERATORS).is_empty());
+    }
+
+    #[test]
+    fn witnesses_can_be_counted_without_enumerating() {
+        let equation = Equation { result: 0, values: vec![0; 80] };
+
+        assert_eq!(count_witnesses(&equation, &PART_ONE_OPERATORS), 2u128.pow(79));
+        assert_eq!(count_witnesses(&Equation::parse("0: 5 3 0"), &PART_ONE_OPERATORS), 2);
+    }
+
+    #[test]
+    fn witnesses_match_forward_enumeration() {
+        property::check(100, |rng| generators::generate(7, rng, 4).unwrap(), |input| {
+            for equation in parse(input.to_string()) {
+                let mut expected: Vec<Vec<&str>> = forward_assignments(&equation.values, &PART_TWO_OPERATORS).into_iter()
+                    .filter(|(value, _)| *value == equation.result)
+                    .map(|(_, symbols)| symbols)
+                    .collect();
+                let mut found: Vec<Vec<&str>> = witnesses(&equation, &PART_TWO_OPERATORS).into_iter().map(|w| w.symbols).collect();
+                expected.sort();
+                found.sort();
+
+                property::assert_same(&equation.to_string(), expected.len() as u128, count_witnesses(&equation, &PART_TWO_OPERATORS))?;
+                property::assert_same(&equation.to_string(), expected, found)?;
+            }
+            Ok(())
+        });
+    }
+}
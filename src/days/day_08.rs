@@ -1,108 +1,130 @@
-﻿use std::collections::{HashMap, HashSet};
+﻿use std::collections::{BTreeMap, HashMap, HashSet};
 use std::fmt::Display;
 use crate::domain::point::Point;
 
-pub fn part_one(input: String) -> impl Display {
-    let grid = Grid::parse(input.to_string());
-    let anti_nodes = calculate_anti_nodes(grid);
-    anti_nodes.len()
+pub fn parse(input: String) -> Grid {
+    Grid::parse(input)
 }
 
-pub fn part_two(input: String) -> impl Display {
-    let grid = Grid::parse(input.to_string());
-    let anti_nodes = calculate_anti_nodes_p2(grid);
-    anti_nodes.len()
+pub fn part_one(grid: &Grid) -> impl Display {
+    AntinodeEngine::new(Harmonics::Multiples(vec![1])).run(grid).count()
 }
 
+pub fn part_two(grid: &Grid) -> impl Display {
+    AntinodeEngine::new(Harmonics::Line).run(grid).count()
+}
 
-fn calculate_anti_nodes(grid: Grid) -> HashSet<Point> {
-    let mut anti_node_signal_points:HashSet<Point> = HashSet::new();
-    let unique_signals = grid.get_unique_signals();
+// Antinodes per frequency for both parts, and where different frequencies land on the same spot.
+pub fn explain(grid: &Grid) -> String {
+    let mut lines: Vec<String> = Vec::new();
+    for (part, harmonics) in [("Part one", Harmonics::Multiples(vec![1])), ("Part two", Harmonics::Line)] {
+        let antinodes = AntinodeEngine::new(harmonics).run(grid);
+        lines.push(format!("{}: {} antinodes, {} shared by more than one frequency", part, antinodes.count(), antinodes.overlapping()));
+        for (frequency, points) in &antinodes.by_frequency {
+            lines.push(format!("  {}: {}", frequency, points.len()));
+        }
+    }
 
-    for unique_signal in unique_signals {
-        let anti_nodes = get_anti_nodes(grid.get_signal_points(&unique_signal));
+    lines.join("\n")
+}
 
-        for anti_node in anti_nodes {
-            if(anti_node.x < 0 || anti_node.y < 0 || anti_node.x >= grid.total_size.x || anti_node.y >= grid.total_size.y)
-            {
-                continue;
+// Which points on the line through a pair of antennas a and b resonate. Every pair is taken both ways round.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Harmonics {
+    // a + (a - b) * k for each k: 1 is the puzzle's point as far beyond a as b is on the other side
+    Multiples(Vec<i32>),
+    // a + (b - a) * p / q for each (p, q), kept only where it lands on a whole cell. (1, 3) and (2, 3) are
+    // the points splitting the gap in three, (2, 1) is the same as multiple 1 seen from b
+    Ratios(Vec<(i32, i32)>),
+    // Every cell in line with the pair, however far out
+    Line
+}
+
+impl Harmonics {
+    fn points(&self, a: Point, b: Point, size: Point) -> Vec<Point> {
+        let gap = b - a;
+        let points = match self {
+            Harmonics::Multiples(multiples) => multiples.iter().map(|k| a - gap.scale(*k)).collect(),
+            Harmonics::Ratios(ratios) => ratios.iter()
+                .filter(|(p, q)| *q != 0 && (gap.x * p) % q == 0 && (gap.y * p) % q == 0)
+                .map(|(p, q)| a + Point::new(gap.x * p / q, gap.y * p / q))
+                .collect(),
+            Harmonics::Line => {
+                let step = gap.normalize_to_line();
+                let mut points = Vec::new();
+                for direction in [step, step.scale(-1)] {
+                    let mut position = a;
+                    while position.within_bounds(size, Point::new(0, 0)) {
+                        points.push(position);
+                        position = position + direction;
+                    }
+                }
+                points
             }
-            anti_node_signal_points.insert(anti_node);
+        };
 
-        }
+        points.into_iter().filter(|point| point.within_bounds(size, Point::new(0, 0))).collect()
     }
-    anti_node_signal_points
 }
 
-fn calculate_anti_nodes_p2(grid: Grid) -> HashSet<Point> {
-    let mut anti_node_signal_points:HashSet<Point> = HashSet::new();
-    let unique_signals = grid.get_unique_signals();
+pub struct AntinodeEngine {
+    harmonics: Harmonics
+}
 
-    for unique_signal in unique_signals {
-        let anti_nodes = get_anti_nodes_p2(grid.get_signal_points(&unique_signal), grid.total_size);
+impl AntinodeEngine {
+    pub fn new(harmonics: Harmonics) -> Self {
+        AntinodeEngine { harmonics }
+    }
 
-        for anti_node in anti_nodes {
-            if(anti_node.x < 0 || anti_node.y < 0 || anti_node.x >= grid.total_size.x || anti_node.y >= grid.total_size.y)
-            {
-                continue;
+    pub fn run(&self, grid: &Grid) -> Antinodes {
+        let mut by_frequency: BTreeMap<char, HashSet<Point>> = BTreeMap::new();
+
+        for frequency in grid.get_unique_signals() {
+            let antennas = grid.get_signal_points(&frequency);
+            let mut points: HashSet<Point> = HashSet::new();
+            for a in &antennas {
+                for b in &antennas {
+                    if a != b {
+                        points.extend(self.harmonics.points(*a, *b, grid.total_size));
+                    }
+                }
             }
-            anti_node_signal_points.insert(anti_node);
-
+            by_frequency.insert(frequency, points);
         }
+
+        Antinodes { by_frequency }
     }
-    anti_node_signal_points
 }
 
-fn get_anti_nodes(signals: Vec<Point>) -> Vec<Point> {
-    let cloned = &signals.clone();
-    let mut anti_nodes: Vec<Point> = Vec::new();
-    for signal in signals {
-        for other_signal in cloned {
-            // Draw a line between signal and other signal
-            // Take the difference between the two and then double it
-            // Take the point that it leads to and add to the list.
-            // Move on
-            if (signal == *other_signal) {
-                continue;
-            }
+pub struct Antinodes {
+    by_frequency: BTreeMap<char, HashSet<Point>>
+}
 
-            let vector = signal - *other_signal;
-            anti_nodes.push(vector.scale(2) + *other_signal);
-        }
+impl Antinodes {
+    // Distinct cells holding an antinode of any frequency
+    pub fn count(&self) -> usize {
+        self.overlap_counts().len()
     }
 
-    anti_nodes
-}
-
-fn get_anti_nodes_p2(signals: Vec<Point>, grid_size:Point) -> Vec<Point> {
-    let cloned = &signals.clone();
-    let mut anti_nodes: Vec<Point> = Vec::new();
-    for signal in signals {
-        for other_signal in cloned {
-            if (signal == *other_signal) {
-                continue;
-            }
+    pub fn for_frequency(&self, frequency: char) -> Option<&HashSet<Point>> {
+        self.by_frequency.get(&frequency)
+    }
 
-            let vector = signal - *other_signal;
-            let direction = vector.normalize_to_line();
-            let mut position = *other_signal;
-            loop {
-                position = position + direction;
-                if position.within_bounds(grid_size, Point::new(0,0)) {
-                    anti_nodes.push(position);
-                }
-                else{
-                    break;
-                }
-            }
+    // How many frequencies put an antinode on each cell.
+    pub fn overlap_counts(&self) -> HashMap<Point, usize> {
+        let mut counts: HashMap<Point, usize> = HashMap::new();
+        for point in self.by_frequency.values().flatten() {
+            *counts.entry(*point).or_insert(0) += 1;
         }
+        counts
     }
 
-    anti_nodes
+    pub fn overlapping(&self) -> usize {
+        self.overlap_counts().values().filter(|count| **count > 1).count()
+    }
 }
 
-
-struct Grid {
+pub struct Grid {
     grid: HashMap<Point, char>,
     total_size: Point,
 }
@@ -153,22 +175,17 @@ impl Grid {
 
 #[cfg(test)]
 mod tests {
-    use crate::days::day_08::{calculate_anti_nodes, calculate_anti_nodes_p2, get_anti_nodes, Grid};
+    use crate::{generators, property};
+    use crate::days::day_08::{AntinodeEngine, Grid, Harmonics};
     use crate::domain::point::Point;
 
     #[test]
     fn can_find_anti_nodes_for_points() {
-        let mut nodes:Vec<Point> = Vec::new();
+        let harmonics = Harmonics::Multiples(vec![1]);
+        let size = Point::new(10, 10);
 
-        nodes.push(Point::new(4, 3));
-        nodes.push(Point::new(5, 5));
-
-        let anti_nodes = get_anti_nodes(nodes);
-
-        for anti_node in &anti_nodes {
-            println!("{}", anti_node);
-        }
-        assert_eq!(anti_nodes.len(), 2);
+        assert_eq!(harmonics.points(Point::new(4, 3), Point::new(5, 5), size), vec![Point::new(3, 1)]);
+        assert_eq!(harmonics.points(Point::new(5, 5), Point::new(4, 3), size), vec![Point::new(6, 7)]);
 
     }
 
@@ -211,8 +228,8 @@ mod tests {
 ............"#;
 
         let grid = Grid::parse(input.to_string());
-        let anti_nodes = calculate_anti_nodes(grid);
-        assert_eq!(anti_nodes.len(), 14);
+        let anti_nodes = AntinodeEngine::new(Harmonics::Multiples(vec![1])).run(&grid);
+        assert_eq!(anti_nodes.count(), 14);
     }
 
     #[test]
@@ -231,7 +248,53 @@ mod tests {
 ............"#;
 
         let grid = Grid::parse(input.to_string());
-        let anti_nodes = calculate_anti_nodes_p2(grid);
-        assert_eq!(anti_nodes.len(), 34);
+        let anti_nodes = AntinodeEngine::new(Harmonics::Line).run(&grid);
+        assert_eq!(anti_nodes.count(), 34);
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn interior_ratio_points_and_overlaps_are_reported() {
+        let grid = Grid::parse("a..a..\n......\nb....b".to_string());
+
+        let thirds = AntinodeEngine::new(Harmonics::Ratios(vec![(1, 3), (2, 3)])).run(&grid);
+        assert_eq!(thirds.for_frequency('a').unwrap().len(), 2);
+        assert_eq!(thirds.for_frequency('b').unwrap().len(), 0);
+
+        let halves = AntinodeEngine::new(Harmonics::Ratios(vec![(1, 2), (3, 2)])).run(&grid);
+        assert_eq!(halves.for_frequency('b').unwrap().len(), 0);
+
+        let line = AntinodeEngine::new(Harmonics::Line).run(&grid);
+        assert_eq!(line.count(), 12);
+        assert_eq!(line.overlapping(), 0);
+
+        let crossing = AntinodeEngine::new(Harmonics::Line).run(&Grid::parse("a.b\n...\nb.a".to_string()));
+        assert_eq!(crossing.overlapping(), 1);
+        assert_eq!(crossing.overlap_counts()[&Point::new(1, 1)], 2);
+    }
+
+    #[test]
+    fn line_harmonics_cover_every_collinear_cell() {
+        property::check(100, |rng| {
+            let size = rng.range(2, 12) as usize;
+            generators::generate(8, rng, size).unwrap()
+        }, |input| {
+            let grid = Grid::parse(input.to_string());
+            let mut expected = 0;
+            for x in 0..grid.total_size.x {
+                for y in 0..grid.total_size.y {
+                    let point = Point::new(x, y);
+                    let collinear = grid.get_unique_signals().iter().any(|frequency| {
+                        let antennas = grid.get_signal_points(frequency);
+                        antennas.iter().any(|a| antennas.iter().any(|b| {
+                            let (ab, ap) = (*b - *a, point - *a);
+                            a != b && ab.x * ap.y == ab.y * ap.x
+                        }))
+                    });
+                    expected += collinear as usize;
+                }
+            }
+
+            property::assert_same("antinodes", expected, AntinodeEngine::new(Harmonics::Line).run(&grid).count())
+        });
+    }
+}
@@ -4,15 +4,17 @@ use itertools::all;
 use crate::domain::point::{Point, EAST, NORTH, SOUTH, WEST};
 use crate::tools::parse_numbers_i32;
 
-pub fn part_one(input: String) -> impl Display {
-    let grid = Grid::parse(input.to_string());
-    let result = get_trail_head_scores(&grid);
+pub fn parse(input: String) -> Grid {
+    Grid::parse(input)
+}
+
+pub fn part_one(grid: &Grid) -> impl Display {
+    let result = get_trail_head_scores(grid);
     result
 }
 
-pub fn part_two(input: String) -> impl Display {
-    let grid = Grid::parse(input.to_string());
-    get_trail_head_ratings(&grid)
+pub fn part_two(grid: &Grid) -> impl Display {
+    get_trail_head_ratings(grid)
 }
 
 fn get_trail_head_scores(grid: &Grid) -> usize {
@@ -62,7 +64,7 @@ fn recursive_path_finding(path_fit: PathFit, grid: &Grid, current_point: Point)
 }
 
 
-struct Grid {
+pub struct Grid {
     grid: HashMap<Point, u32>,
     total_size: Point,
     trail_heads: Vec<Point>
@@ -1,11 +1,15 @@
 ﻿use std::fmt::Display;
 use crate::tools::parse_numbers_i128;
 
-pub fn part_one(input: String) -> impl Display {
-    blink_amount(25, StoneArrangement::parse(input))
+pub fn parse(input: String) -> StoneArrangement {
+    StoneArrangement::parse(input)
 }
 
-pub fn part_two(input: String) -> impl Display {
+pub fn part_one(arrangement: &StoneArrangement) -> impl Display {
+    blink_amount(25, arrangement.clone())
+}
+
+pub fn part_two(arrangement: &StoneArrangement) -> impl Display {
     //blink_amount(25, StoneArrangement::parse(input))
     0
 }
@@ -19,7 +23,8 @@ fn blink_amount(blink_amount: i32, mut initial_arrangement: StoneArrangement) ->
     initial_arrangement.stones.len()
 }
 
-struct StoneArrangement {
+#[derive(Clone)]
+pub struct StoneArrangement {
     stones: Vec<i128>
 }
 
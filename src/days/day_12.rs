@@ -4,16 +4,18 @@ use itertools::{Itertools, Positions};
 use crate::domain::point::{Point, EAST, NORTH, SOUTH, WEST};
 use crate::tools::usize_to_i32;
 
-pub fn part_one(input: String) -> impl Display {
-    let grid = Grid::parse(input.to_string());
-    let regions = get_regions(&grid);
+pub fn parse(input: String) -> Grid {
+    Grid::parse(input)
+}
+
+pub fn part_one(grid: &Grid) -> impl Display {
+    let regions = get_regions(grid);
     let result = regions.iter().map(|x| x.get_price()).sum::<i32>();
     result
 }
 
-pub fn part_two(input: String) -> impl Display {
-    let grid = Grid::parse(input.to_string());
-    let regions = get_regions(&grid);
+pub fn part_two(grid: &Grid) -> impl Display {
+    let regions = get_regions(grid);
     let result = regions.iter().map(|x| x.get_price_bulk_discount()).sum::<i32>();
 
     result
@@ -34,7 +36,7 @@ fn get_regions(grid: &Grid) -> Vec<Region> {
     regions
 }
 
-struct Grid {
+pub struct Grid {
     grid: HashMap<Point, char>,
     total_size: Point
 }
@@ -2,14 +2,18 @@
 use itertools::Itertools;
 use std::fmt::Display;
 
-pub fn part_one(input: String) -> impl Display {
+pub fn parse(input: String) -> Vec<CraneInstruction> {
+    parse_all_crane_instructions(input, 0)
+}
+
+pub fn part_one(cranes: &Vec<CraneInstruction>) -> impl Display {
 
-    let total = parse_all_crane_instructions(input.to_string(), 0).iter().map(|x| calculate_token_cost(x.equation())).sum::<i128>();
+    let total = cranes.iter().map(|x| calculate_token_cost(x.equation())).sum::<i128>();
     total
 }
 
-pub fn part_two(input: String) -> impl Display {
-    let total = parse_all_crane_instructions(input.to_string(), 10000000000000).iter().map(|x| calculate_token_cost_without_limit(x.equation())).sum::<i128>();
+pub fn part_two(cranes: &Vec<CraneInstruction>) -> impl Display {
+    let total = cranes.iter().map(|x| calculate_token_cost_without_limit(x.with_prize_offset(10000000000000).equation())).sum::<i128>();
     total
 }
 
@@ -70,7 +74,7 @@ fn get_prize_value(prize_line: &str, prize_add: i128) -> (i128, i128) {
     (x_value + prize_add, y_value + prize_add)
 }
 
-struct CraneInstruction {
+pub struct CraneInstruction {
     a_button: (i128, i128),
     b_button: (i128, i128),
     prize: (i128, i128)
@@ -91,6 +95,10 @@ struct CraneInstruction {
 
 impl CraneInstruction {
 
+    fn with_prize_offset(&self, prize_add: i128) -> Self {
+        CraneInstruction { a_button: self.a_button, b_button: self.b_button, prize: (self.prize.0 + prize_add, self.prize.1 + prize_add) }
+    }
+
     fn equation(&self) -> Option<(i128, i128)> {
         let delta = self.a_button.0 * self.b_button.1 - self.b_button.0 * self.a_button.1;
 
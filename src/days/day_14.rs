@@ -6,20 +6,22 @@ use nom::sequence::{delimited, separated_pair};
 use crate::domain::point::Point;
 use crate::tools::{parse_numbers_i32, usize_to_i32};
 
-pub fn part_one(input: String) -> impl Display {
-    let robots:Vec<Robot> = input.lines().map(|l| Robot::parse(l).unwrap().1).collect();
+pub fn parse(input: String) -> Vec<Robot> {
+    input.lines().map(|l| Robot::parse(l).unwrap().1).collect()
+}
+
+pub fn part_one(robots: &Vec<Robot>) -> impl Display {
     let grid = Grid { size: Point::new(101, 103)};
 
-    let simulated = run_simulation(robots, &grid, 300);
+    let simulated = run_simulation(robots.clone(), &grid, 300);
 
     let safety = calculate_safety_factor(simulated, &grid);
     safety
 }
 
-pub fn part_two(input: String) -> impl Display {
-    let robots:Vec<Robot> = input.lines().map(|l| Robot::parse(l).unwrap().1).collect();
+pub fn part_two(robots: &Vec<Robot>) -> impl Display {
     let grid = Grid { size: Point::new(101, 103)};
-    let simulated = run_simulation_with_visual(robots, &grid, 8300);
+    let simulated = run_simulation_with_visual(robots.clone(), &grid, 8300);
     0
 }
 
@@ -146,7 +148,8 @@ fn calculate_safety_factor(robots: Vec<Robot>, grid: &Grid) -> i32 {
 
 }
 
-struct Robot {
+#[derive(Clone)]
+pub struct Robot {
     position: Point,
     velocity: Point
 }
@@ -3,17 +3,23 @@ use std::{fmt, result};
 use std::fmt::{write, Display};
 use crate::domain::point::{Point, EAST, NORTH, SOUTH, WEST};
 
-pub fn part_one(input: String) -> impl Display {
+pub fn parse(input: String) -> Warehouse {
     let mut split = input.split("\n\n");
-    let mut grid = Grid::parse(split.nth(0).unwrap());
+    let grid = Grid::parse(split.nth(0).unwrap());
     let instructions = parse_instructions(split.nth(0).unwrap());
 
+    Warehouse { grid, instructions }
+}
+
+pub fn part_one(warehouse: &Warehouse) -> impl Display {
+    let mut grid = warehouse.grid.clone();
+
     grid.print();
-    let result = run_simulation(&mut grid, instructions);
+    let result = run_simulation(&mut grid, warehouse.instructions.clone());
     //instructions.iter().for_each(|i| println!("{}", i));
     result
 }
-pub fn part_two(input: String) -> impl Display {
+pub fn part_two(warehouse: &Warehouse) -> impl Display {
     // I'm pretty sure I can do this one, but oh boy would it take some rewriting to get there of my Part 1 solution. Might come back to it.
     0
 }
@@ -116,10 +122,16 @@ impl fmt::Display for Entity {
     }
 }
 
+#[derive(Clone)]
 struct Grid {
     grid: HashMap<Point, Entity>,
     size: Point
 }
+
+pub struct Warehouse {
+    grid: Grid,
+    instructions: Vec<Point>
+}
 impl Grid {
     fn parse(input: &str) -> Self {
         let mut y_index = (input.lines().count() as i32);
@@ -4,16 +4,17 @@ use itertools::Itertools;
 use priority_queue::PriorityQueue;
 use crate::domain::point::{Point, EAST};
 
-pub fn part_one(input: String) -> impl Display {
-    let grid = Grid::parse(input.clone());
+pub fn parse(input: String) -> Grid {
+    Grid::parse(input)
+}
 
-    let result = non_recursive_path_finding(PathFit { path: vec![grid.start], cost: 0 }, &grid, grid.start, EAST);
-    result.iter().map(|x| x.cost).min().unwrap();
+pub fn part_one(grid: &Grid) -> impl Display {
+    let result = non_recursive_path_finding(PathFit { path: vec![grid.start], cost: 0 }, grid, grid.start, EAST);
+    result.iter().map(|x| x.cost).min().unwrap()
 }
 
-pub fn part_two(input: String) -> impl Display {
-    let grid = Grid::parse(input.clone());
-    let result = non_recursive_path_finding(PathFit { path: vec![grid.start], cost: 0 }, &grid, grid.start, EAST);
+pub fn part_two(grid: &Grid) -> impl Display {
+    let result = non_recursive_path_finding(PathFit { path: vec![grid.start], cost: 0 }, grid, grid.start, EAST);
     let mut point_hash:HashSet<Point> = HashSet::new();
 
     for path in result {
@@ -292,7 +293,7 @@ struct PathFit {
     cost: i32
 }
 
-struct Grid {
+pub struct Grid {
     nodes: HashMap<Point, char>,
     total_size: Point,
     start: Point,
@@ -347,8 +348,40 @@ impl Grid {
 
 #[cfg(test)]
 mod tests {
+    use std::collections::{BinaryHeap, HashMap};
+    use std::cmp::Reverse;
     use crate::days::day_16::{a_star_path_finding, non_recursive_path_finding, Grid, PathFit};
-    use crate::domain::point::EAST;
+    use crate::domain::point::{Point, EAST, NORTH, SOUTH, WEST};
+    use crate::{generators, property};
+
+    // Reference: plain Dijkstra over (position, facing) states, moving costs 1 and turning costs 1000.
+    fn lowest_score(grid: &Grid) -> Option<i32> {
+        let mut best: HashMap<(Point, Point), i32> = HashMap::new();
+        let mut queue = BinaryHeap::new();
+        queue.push(Reverse((0, grid.start, EAST)));
+
+        while let Some(Reverse((cost, position, facing))) = queue.pop() {
+            if position == grid.end {
+                return Some(cost);
+            }
+            if best.get(&(position, facing)).is_some_and(|b| *b <= cost) {
+                continue;
+            }
+            best.insert((position, facing), cost);
+
+            let ahead = position + facing;
+            if grid.get_neighbors(position).contains(&ahead) {
+                queue.push(Reverse((cost + 1, ahead, facing)));
+            }
+            for turn in [NORTH, EAST, SOUTH, WEST] {
+                if turn != facing && turn != Point::new(0, 0) - facing {
+                    queue.push(Reverse((cost + 1000, position, turn)));
+                }
+            }
+        }
+
+        None
+    }
 
     #[test]
     fn can_get_path_to_end_with_a_star(){
@@ -430,4 +463,17 @@ mod tests {
         assert_eq!(result, 11048);
     }
 
-}
\ No newline at end of file
+    #[test]
+    fn path_finding_matches_reference_search() {
+        property::check(300, |rng| {
+            let width = rng.range(5, 12);
+            let height = rng.range(5, 12);
+            generators::maze(rng, width, height)
+        }, |input| {
+            let grid = Grid::parse(input.to_string());
+            let result = non_recursive_path_finding(PathFit { path: vec![grid.start], cost: 0 }, &grid, grid.start, EAST);
+
+            property::assert_same("lowest score", lowest_score(&grid), result.iter().map(|x| x.cost).min())
+        });
+    }
+}
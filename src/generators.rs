@@ -0,0 +1,289 @@
+use crate::tools::Rng;
+
+// Builds a valid puzzle input for a day. `size` is the grid width/height for map days
+// and the number of entries (equations, machines, robots) for list days.
+pub fn generate(day: i32, rng: &mut Rng, size: usize) -> Option<String> {
+    let size = size.max(1) as i64;
+    match day {
+        5 => Some(page_ordering(rng, size)),
+        6 => Some(guard_map(rng, size.max(3), size.max(3), 3)),
+        7 => Some(calibration_equations(rng, size)),
+        8 => Some(antenna_map(rng, size.max(2))),
+        10 => Some(topographic_map(rng, size)),
+        12 => Some(garden_plots(rng, size)),
+        13 => Some(claw_machines(rng, size)),
+        14 => Some(robots(rng, size)),
+        15 => Some(warehouse(rng, size.max(4))),
+        16 => Some(maze(rng, size.max(5), size.max(5))),
+        _ => None
+    }
+}
+
+pub fn grid(rng: &mut Rng, width: i64, height: i64, cells: &[(char, u32)]) -> String {
+    let total: u32 = cells.iter().map(|(_, weight)| weight).sum();
+    let mut lines: Vec<String> = Vec::new();
+
+    for _ in 0..height {
+        let line = (0..width).map(|_| {
+            let mut roll = rng.range(0, total as i64) as u32;
+            for (cell, weight) in cells {
+                if roll < *weight {
+                    return *cell;
+                }
+                roll -= weight;
+            }
+            cells[0].0
+        }).collect();
+        lines.push(line);
+    }
+
+    lines.join("\n")
+}
+
+// Places a glyph on a random cell that currently holds `on`, if there is one.
+pub fn place(rng: &mut Rng, grid: &str, glyph: char, on: char) -> String {
+    let candidates: Vec<usize> = grid.char_indices().filter(|(_, c)| *c == on).map(|(i, _)| i).collect();
+    if candidates.is_empty() {
+        return grid.to_string();
+    }
+
+    let index = *rng.choose(&candidates);
+    let mut placed = grid.to_string();
+    placed.replace_range(index..index + on.len_utf8(), &glyph.to_string());
+    placed
+}
+
+pub fn guard_map(rng: &mut Rng, width: i64, height: i64, obstacle_percent: u32) -> String {
+    let map = grid(rng, width, height, &[('.', 100 - obstacle_percent), ('#', obstacle_percent)]);
+    place(rng, &map, '^', '.')
+}
+
+// Carves a maze with a depth first walk over the odd cells, then knocks out a few extra walls
+// so there is more than one route. S sits in the bottom left and E in the top right.
+pub fn maze(rng: &mut Rng, width: i64, height: i64) -> String {
+    let width = (width | 1) as usize;
+    let height = (height | 1) as usize;
+    let mut cells = vec![vec!['#'; width]; height];
+
+    let mut stack = vec![(1usize, height - 2)];
+    cells[height - 2][1] = '.';
+    while let Some(&(x, y)) = stack.last() {
+        let options: Vec<(usize, usize)> = [(0i64, -2i64), (2, 0), (0, 2), (-2, 0)].iter()
+            .map(|(dx, dy)| (x as i64 + dx, y as i64 + dy))
+            .filter(|(nx, ny)| *nx > 0 && *ny > 0 && *nx < width as i64 - 1 && *ny < height as i64 - 1)
+            .map(|(nx, ny)| (nx as usize, ny as usize))
+            .filter(|(nx, ny)| cells[*ny][*nx] == '#')
+            .collect();
+
+        if options.is_empty() {
+            stack.pop();
+            continue;
+        }
+
+        let (nx, ny) = *rng.choose(&options);
+        cells[(y + ny) / 2][(x + nx) / 2] = '.';
+        cells[ny][nx] = '.';
+        stack.push((nx, ny));
+    }
+
+    for y in 1..height - 1 {
+        for x in 1..width - 1 {
+            let horizontal = cells[y][x - 1] == '.' && cells[y][x + 1] == '.';
+            let vertical = cells[y - 1][x] == '.' && cells[y + 1][x] == '.';
+            if cells[y][x] == '#' && (horizontal || vertical) && rng.chance(10) {
+                cells[y][x] = '.';
+            }
+        }
+    }
+
+    cells[height - 2][1] = 'S';
+    cells[1][width - 2] = 'E';
+
+    cells.iter().map(|line| line.iter().collect::<String>()).collect::<Vec<String>>().join("\n")
+}
+
+// Rules cover every pair of pages in a hidden order, like the real input, so every update has one correct order.
+fn page_ordering(rng: &mut Rng, size: i64) -> String {
+    let page_count = size.clamp(5, 90) as usize;
+    let mut pages: Vec<i64> = (10..10 + page_count as i64).collect();
+    shuffle(rng, &mut pages);
+
+    let mut rules: Vec<String> = Vec::new();
+    for i in 0..pages.len() {
+        for j in i + 1..pages.len() {
+            rules.push(format!("{}|{}", pages[i], pages[j]));
+        }
+    }
+    shuffle(rng, &mut rules);
+
+    let mut updates: Vec<String> = Vec::new();
+    for _ in 0..size * 4 {
+        // Updates need an odd length to have a middle page
+        let largest_odd = (page_count - 1) | 1;
+        let length = ((rng.range(2, 12) * 2 + 1) as usize).min(largest_odd);
+        let mut update = pages.clone();
+        shuffle(rng, &mut update);
+        update.truncate(length);
+        if rng.chance(50) {
+            update.sort_by_key(|page| pages.iter().position(|p| p == page));
+        }
+        updates.push(update.iter().map(|p| p.to_string()).collect::<Vec<String>>().join(","));
+    }
+
+    format!("{}\n\n{}", rules.join("\n"), updates.join("\n"))
+}
+
+fn calibration_equations(rng: &mut Rng, size: i64) -> String {
+    let mut lines: Vec<String> = Vec::new();
+    for _ in 0..size {
+        let count = rng.range(2, 11);
+        let values: Vec<i128> = (0..count).map(|_| rng.range(1, 100) as i128).collect();
+
+        let mut result = values[0];
+        for value in &values[1..] {
+            result = match rng.range(0, 3) {
+                0 => result + value,
+                1 => result * value,
+                _ => format!("{}{}", result, value).parse().unwrap()
+            };
+        }
+        if rng.chance(30) {
+            result += rng.range(1, 10) as i128;
+        }
+
+        let values: Vec<String> = values.iter().map(|v| v.to_string()).collect();
+        lines.push(format!("{}: {}", result, values.join(" ")));
+    }
+
+    lines.join("\n")
+}
+
+fn antenna_map(rng: &mut Rng, size: i64) -> String {
+    let frequencies: Vec<char> = ('0'..='9').chain('a'..='z').chain('A'..='Z').collect();
+    let mut map = grid(rng, size, size, &[('.', 1)]);
+    for _ in 0..(size * size / 20).max(2) {
+        let frequency = *rng.choose(&frequencies[..(size as usize / 4).clamp(1, frequencies.len())]);
+        map = place(rng, &map, frequency, '.');
+    }
+
+    map
+}
+
+// Heights mostly climb along the diagonal so there are long trails to follow.
+fn topographic_map(rng: &mut Rng, size: i64) -> String {
+    let mut lines: Vec<String> = Vec::new();
+    for y in 0..size {
+        let line: String = (0..size).map(|x| {
+            let height = (x + y + rng.range(0, 2)) % 10;
+            char::from_digit(height as u32, 10).unwrap()
+        }).collect();
+        lines.push(line);
+    }
+
+    lines.join("\n")
+}
+
+// Plants copy a neighbour most of the time so the map forms blobs rather than single cells.
+fn garden_plots(rng: &mut Rng, size: i64) -> String {
+    let plants: Vec<char> = ('A'..='Z').collect();
+    let size = size as usize;
+    let mut cells = vec![vec!['A'; size]; size];
+
+    for y in 0..size {
+        for x in 0..size {
+            cells[y][x] = if x > 0 && rng.chance(45) {
+                cells[y][x - 1]
+            } else if y > 0 && rng.chance(60) {
+                cells[y - 1][x]
+            } else {
+                *rng.choose(&plants)
+            };
+        }
+    }
+
+    cells.iter().map(|line| line.iter().collect::<String>()).collect::<Vec<String>>().join("\n")
+}
+
+fn claw_machines(rng: &mut Rng, size: i64) -> String {
+    let mut machines: Vec<String> = Vec::new();
+    for _ in 0..size {
+        let a = (rng.range(10, 100), rng.range(10, 100));
+        let b = (rng.range(10, 100), rng.range(10, 100));
+        let prize = if rng.chance(70) {
+            let presses = (rng.range(0, 101), rng.range(0, 101));
+            (a.0 * presses.0 + b.0 * presses.1, a.1 * presses.0 + b.1 * presses.1)
+        } else {
+            (rng.range(1000, 20000), rng.range(1000, 20000))
+        };
+        machines.push(format!("Button A: X+{}, Y+{}\nButton B: X+{}, Y+{}\nPrize: X={}, Y={}", a.0, a.1, b.0, b.1, prize.0, prize.1));
+    }
+
+    machines.join("\n\n")
+}
+
+fn robots(rng: &mut Rng, size: i64) -> String {
+    (0..size).map(|_| format!("p={},{} v={},{}", rng.range(0, 101), rng.range(0, 103), rng.range(-100, 101), rng.range(-100, 101)))
+        .collect::<Vec<String>>().join("\n")
+}
+
+fn warehouse(rng: &mut Rng, size: i64) -> String {
+    let size = size as usize;
+    let mut cells: Vec<Vec<char>> = grid(rng, size as i64, size as i64, &[('.', 75), ('O', 20), ('#', 5)])
+        .lines().map(|line| line.chars().collect()).collect();
+    for y in 0..size {
+        for x in 0..size {
+            if y == 0 || x == 0 || y == size - 1 || x == size - 1 {
+                cells[y][x] = '#';
+            }
+        }
+    }
+    cells[size / 2][size / 2] = '@';
+    let map = cells.iter().map(|line| line.iter().collect::<String>()).collect::<Vec<String>>().join("\n");
+
+    let moves: String = (0..size * size).map(|_| *rng.choose(&['<', '>', '^', 'v'])).collect();
+    let move_lines: Vec<String> = moves.chars().collect::<Vec<char>>().chunks(70).map(|c| c.iter().collect()).collect();
+
+    format!("{}\n\n{}", map, move_lines.join("\n"))
+}
+
+fn shuffle<T>(rng: &mut Rng, items: &mut Vec<T>) {
+    for i in (1..items.len()).rev() {
+        let j = rng.range(0, i as i64 + 1) as usize;
+        items.swap(i, j);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::input::{expectations_for, normalize, validate};
+    use crate::tools::Rng;
+    use super::{generate, page_ordering};
+
+    #[test]
+    fn generated_inputs_have_the_expected_shape() {
+        for day in [5, 6, 7, 8, 10, 12, 13, 14, 15, 16] {
+            for size in [1, 7, 40] {
+                let input = generate(day, &mut Rng::new(day as u64), size).unwrap();
+
+                assert_eq!(normalize(&input), input, "day {} size {}", day, size);
+                assert_eq!(validate(&input, &expectations_for(day)), Ok(()), "day {} size {}", day, size);
+            }
+        }
+    }
+
+    #[test]
+    fn generated_inputs_are_reproducible_from_the_seed() {
+        assert_eq!(generate(16, &mut Rng::new(7), 21), generate(16, &mut Rng::new(7), 21));
+        assert_ne!(generate(16, &mut Rng::new(7), 21), generate(16, &mut Rng::new(8), 21));
+    }
+
+    #[test]
+    fn page_ordering_updates_have_a_middle_page() {
+        for size in [5, 6, 10, 22, 90] {
+            let input = page_ordering(&mut Rng::new(size as u64), size);
+            let updates = input.split("\n\n").nth(1).unwrap();
+
+            assert!(updates.lines().all(|update| update.split(',').count() % 2 == 1), "size {}", size);
+        }
+    }
+}
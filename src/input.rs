@@ -0,0 +1,123 @@
+use std::fs;
+use std::path::Path;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Expectation {
+    BlockCount(usize),
+    LinesPerBlock(usize),
+    RectangularBlock(usize)
+}
+
+pub fn expectations_for(day: i32) -> Vec<Expectation> {
+    match day {
+        4 | 6 | 8 | 10 | 12 | 16 => vec![Expectation::BlockCount(1), Expectation::RectangularBlock(0)],
+        5 => vec![Expectation::BlockCount(2)],
+        13 => vec![Expectation::LinesPerBlock(3)],
+        15 => vec![Expectation::BlockCount(2), Expectation::RectangularBlock(0)],
+        1 | 2 | 3 | 7 | 11 | 14 => vec![Expectation::BlockCount(1)],
+        _ => vec![]
+    }
+}
+
+pub fn load_input(path: &Path, day: i32) -> Result<String, String> {
+    let raw = fs::read_to_string(path).map_err(|e| format!("Could not read {}: {}", path.display(), e))?;
+    let input = normalize(&raw);
+    validate(&input, &expectations_for(day)).map_err(|e| format!("{}: {}", path.display(), e))?;
+
+    Ok(input)
+}
+
+// Strips the BOM, unifies line endings and drops trailing whitespace so the days can split on "\n\n" safely.
+pub fn normalize(raw: &str) -> String {
+    let without_bom = raw.strip_prefix('\u{feff}').unwrap_or(raw);
+    let unified = without_bom.replace("\r\n", "\n").replace('\r', "\n");
+
+    let lines: Vec<&str> = unified.lines().map(|line| line.trim_end()).collect();
+    let last_content = lines.iter().rposition(|line| !line.is_empty());
+
+    match last_content {
+        None => String::new(),
+        Some(index) => lines[..=index].join("\n")
+    }
+}
+
+pub fn validate(input: &str, expectations: &[Expectation]) -> Result<(), String> {
+    if input.is_empty() {
+        return Err("input is empty".to_string());
+    }
+
+    let blocks: Vec<&str> = input.split("\n\n").collect();
+
+    for expectation in expectations {
+        match expectation {
+            Expectation::BlockCount(count) => {
+                if blocks.len() != *count {
+                    return Err(format!("expected {} blank line separated block(s), found {}", count, blocks.len()));
+                }
+            }
+            Expectation::LinesPerBlock(count) => {
+                for (index, block) in blocks.iter().enumerate() {
+                    let lines = block.lines().count();
+                    if lines != *count {
+                        return Err(format!("block {} has {} line(s), expected {}", index + 1, lines, count));
+                    }
+                }
+            }
+            Expectation::RectangularBlock(block_index) => {
+                let Some(block) = blocks.get(*block_index) else {
+                    return Err(format!("block {} is missing", block_index + 1));
+                };
+                check_rectangular(block)?;
+            }
+        }
+    }
+
+    Ok(())
+}
+
+fn check_rectangular(block: &str) -> Result<(), String> {
+    let width = block.lines().next().map(|line| line.chars().count()).unwrap_or(0);
+    for (y, line) in block.lines().enumerate() {
+        let line_width = line.chars().count();
+        if line_width != width {
+            return Err(format!("grid is not rectangular: line {} has {} column(s), expected {}", y + 1, line_width, width));
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{normalize, validate, Expectation};
+
+    #[test]
+    fn normalizing_strips_bom_and_line_endings() {
+        let input = "\u{feff}47|53\r\n97|13  \r\n\r\n75,47,61\r\n\r\n\r\n";
+
+        assert_eq!(normalize(input), "47|53\n97|13\n\n75,47,61");
+    }
+
+    #[test]
+    fn rectangular_grids_pass_validation() {
+        let input = normalize("..#\n.^.\n...\n");
+
+        assert_eq!(validate(&input, &[Expectation::BlockCount(1), Expectation::RectangularBlock(0)]), Ok(()));
+    }
+
+    #[test]
+    fn ragged_grids_fail_validation() {
+        let input = normalize("..#\n.^\n...\n");
+
+        let result = validate(&input, &[Expectation::RectangularBlock(0)]);
+        assert_eq!(result, Err("grid is not rectangular: line 2 has 2 column(s), expected 3".to_string()));
+    }
+
+    #[test]
+    fn block_counts_are_checked() {
+        let input = normalize("47|53\n97|13\n");
+
+        assert!(validate(&input, &[Expectation::BlockCount(2)]).is_err());
+        assert!(validate("", &[]).is_err());
+    }
+}
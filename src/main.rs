@@ -1,92 +1,365 @@
 #![allow(warnings)]
 use crate::days::*;
-use std::{env, fs};
+use std::env;
 use std::fmt::Display;
 use std::path::Path;
-use std::time::Instant;
-use clap::Parser;
-
+use std::time::{Duration, Instant};
+use clap::{Parser, Subcommand};
+use crate::submit::{CurlClient, SubmissionHistory};
+use crate::tools::Rng;
 
 pub mod days;
 mod tools;
 mod domain;
+mod submit;
+mod input;
+mod generators;
+#[cfg(test)]
+mod property;
 
 static ANSI_ITALIC: &str = "\x1b[3m";
 static ANSI_BOLD: &str = "\x1b[1m";
 static ANSI_RESET: &str = "\x1b[0m";
+static ANSI_CLEAR: &str = "\x1b[2J\x1b[H";
 
 #[derive(Parser)]
+#[command(args_conflicts_with_subcommands = true)]
 struct RunArgument {
-    day: Option<i32>
+    day: Option<i32>,
+    #[command(subcommand)]
+    command: Option<Command>
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Run a solution and submit its answer, recording the result in the local history
+    Submit {
+        day: i32,
+        part: i32
+    },
+    /// Print a breakdown of how a day's answer was reached
+    Explain {
+        day: i32
+    },
+    /// Print a random, valid puzzle input for stress testing a day
+    Generate {
+        day: i32,
+        #[arg(long, default_value_t = 0)]
+        seed: u64,
+        /// Grid width and height for map puzzles, number of entries for list puzzles
+        #[arg(long, default_value_t = 50)]
+        size: usize
+    },
+    /// Total the day 7 calibration equations that a chosen set of operators can satisfy
+    Calibrate {
+        /// Comma separated operators from add, multiply, concatenate, subtract, xor and power
+        #[arg(long, default_value = "add,multiply,concatenate")]
+        operators: String,
+        /// Print every operator assignment that satisfies each equation
+        #[arg(long)]
+        witnesses: bool,
+        /// Print how many assignments satisfy each equation
+        #[arg(long, conflicts_with = "witnesses")]
+        count: bool
+    },
+    /// Animate the day 6 guard's walk frame by frame
+    Replay {
+        /// Milliseconds between frames
+        #[arg(long, default_value_t = 100)]
+        delay: u64,
+        /// Step to jump to before playing
+        #[arg(long, default_value_t = 0)]
+        from: usize,
+        /// Wait for Enter between frames. Type a step number to jump to it, or q to quit
+        #[arg(long)]
+        step: bool,
+        /// Extra obstacle as column,row from the top left, to see the loop it creates
+        #[arg(long, value_parser = parse_cell)]
+        obstacle: Option<(i32, i32)>,
+        /// Which guard to follow, in reading order, when the map has several
+        #[arg(long, default_value_t = 0)]
+        guard: usize,
+        /// How the guard turns at an obstacle: right, left or reverse
+        #[arg(long, default_value = "right")]
+        turn: day_06::TurnPolicy
+    }
+}
+
+struct TimedAnswer {
+    part: i32,
+    answer: Result<String, String>,
+    elapsed: Duration
+}
+
+struct DayRun {
+    parse_elapsed: Duration,
+    answers: Vec<TimedAnswer>
+}
+
+// Parses the input once, then runs the requested parts against the shared model.
+type Solution = fn(String, &[i32]) -> DayRun;
+
+fn infallible<T: Display>(answer: T) -> Result<String, String> {
+    Ok(answer.to_string())
+}
+
+fn fallible<T: Display>(answer: Result<T, String>) -> Result<String, String> {
+    answer.map(|a| a.to_string())
 }
 
-macro_rules! print_style_result {
-    ($day:path, $input:expr, $day_name:expr) => {{
+// Days whose parts can fail return Result<_, String> and are registered with `fallible`.
+macro_rules! solution {
+    ($day:path) => {
+        solution!($day, infallible)
+    };
+    ($day:path, $into_answer:ident) => {{
         use $day::*;
-        println!("----");
-        println!("🎄 {}{}{} 🎄", ANSI_BOLD, $day_name, ANSI_RESET);
-        println!("🎄 {}Part 1{} 🎄", ANSI_BOLD, ANSI_RESET);
-        print_result(part_one, $input);
-        println!("🎄 {}Part 2{} 🎄", ANSI_BOLD, ANSI_RESET);
-        print_result(part_two, $input);
-        println!("----");
+        let run: Solution = |input, parts| {
+            let timer = Instant::now();
+            let model = parse(input);
+            let parse_elapsed = timer.elapsed();
+
+            let answers = parts.iter().map(|part| {
+                let timer = Instant::now();
+                let answer = match part {
+                    1 => $into_answer(part_one(&model)),
+                    _ => $into_answer(part_two(&model))
+                };
+                TimedAnswer { part: *part, answer, elapsed: timer.elapsed() }
+            }).collect();
+
+            DayRun { parse_elapsed, answers }
+        };
+        run
     }};
 }
 
-
 fn main() {
     let parse_result = RunArgument::parse();
 
-    match parse_result.day {
-        Some(day) => print_specific_day(day),
-        None => print_all_days()
+    match parse_result.command {
+        Some(Command::Submit { day, part }) => submit_specific_part(day, part),
+        Some(Command::Explain { day }) => explain_specific_day(day),
+        Some(Command::Generate { day, seed, size }) => match generators::generate(day, &mut Rng::new(seed), size) {
+            Some(input) => println!("{}", input),
+            None => println!("Day {} has no input generator", day)
+        },
+        Some(Command::Calibrate { operators, witnesses, count }) => calibrate_equations(&operators, witnesses, count),
+        Some(Command::Replay { delay, from, step, obstacle, guard, turn }) => replay_guard(delay, from, step, obstacle, guard, turn),
+        None => match parse_result.day {
+            Some(day) => print_specific_day(day),
+            None => print_all_days()
+        }
     }
     env::set_var("RUST_BACKTRACE", "1");
 }
 
+fn get_solution(day: i32) -> Option<Solution> {
+    match day {
+        1 => Some(solution!(day_01, fallible)),
+        2 => Some(solution!(day_02)),
+        3 => Some(solution!(day_03, fallible)),
+        4 => Some(solution!(day_04)),
+        5 => Some(solution!(day_05, fallible)),
+        6 => Some(solution!(day_06)),
+        7 => Some(solution!(day_07)),
+        8 => Some(solution!(day_08)),
+        10 => Some(solution!(day_10)),
+        11 => Some(solution!(day_11)),
+        12 => Some(solution!(day_12)),
+        13 => Some(solution!(day_13)),
+        14 => Some(solution!(day_14)),
+        15 => Some(solution!(day_15)),
+        16 => Some(solution!(day_16)),
+        _ => None
+    }
+}
+
 fn print_all_days(){
     for i in 1..25 {
         print_specific_day(i)
     }
 }
+
 fn print_specific_day(day: i32) {
+    let Some(solution) = get_solution(day) else {
+        return;
+    };
+    let input = match load_file(day) {
+        Ok(input) => input,
+        Err(message) => {
+            println!("Day {} skipped: {}", day, message);
+            return;
+        }
+    };
+
+    println!("----");
+    println!("🎄 {}Day {}{} 🎄", ANSI_BOLD, day, ANSI_RESET);
+    let run = solution(input, &[1, 2]);
+    println!("🎄 {}Parse{} 🎄", ANSI_BOLD, ANSI_RESET);
+    print_elapsed(run.parse_elapsed);
+    for answer in run.answers {
+        println!("🎄 {}Part {}{} 🎄", ANSI_BOLD, answer.part, ANSI_RESET);
+        match &answer.answer {
+            Ok(result) => print_result(result, answer.elapsed),
+            Err(message) => print_result(format!("error: {}", message), answer.elapsed)
+        }
+    }
+    println!("----");
+}
+
+fn submit_specific_part(day: i32, part: i32) {
+    let Some(solution) = get_solution(day) else {
+        println!("Day {} has no solution to submit", day);
+        return;
+    };
+    if part != 1 && part != 2 {
+        println!("Part must be 1 or 2, got {}", part);
+        return;
+    }
+
+    let input = match load_file(day) {
+        Ok(input) => input,
+        Err(message) => {
+            println!("{}", message);
+            return;
+        }
+    };
+    let run = solution(input, &[part]);
+    let answer = match &run.answers[0].answer {
+        Ok(answer) => answer,
+        Err(message) => {
+            println!("Day {} Part {} failed: {}", day, part, message);
+            return;
+        }
+    };
+    println!("Day {} Part {} answer: {}", day, part, answer);
+
+    let client = match CurlClient::from_env() {
+        Ok(client) => client,
+        Err(message) => {
+            println!("{}", message);
+            return;
+        }
+    };
+    let mut history = SubmissionHistory::load(Path::new("puzzle_inputs").join("submissions.txt"));
+
+    match submit::submit_answer(&client, &mut history, day, part, answer) {
+        Ok(submitted) => {
+            println!("{}", submitted.outcome);
+            if let Some(warning) = submitted.warning {
+                println!("Warning: {}", warning);
+            }
+        }
+        Err(message) => println!("Not submitted: {}", message)
+    }
+}
+
+fn explain_specific_day(day: i32) {
+    let input = match load_file(day) {
+        Ok(input) => input,
+        Err(message) => {
+            println!("{}", message);
+            return;
+        }
+    };
+
     match day {
-        1 => print_style_result!(day_01, load_file("day01_input.txt"), "Day 1"),
-        2 => print_style_result!(day_02, load_file("day02_input.txt"), "Day 2"),
-        3 => print_style_result!(day_03, load_file("day03_input.txt"), "Day 3"),
-        4 => print_style_result!(day_04, load_file("day04_input.txt"), "Day 4"),
-        5 => print_style_result!(day_05, load_file("day05_input.txt"), "Day 5"),
-        6 => print_style_result!(day_06, load_file("day06_input.txt"), "Day 6"),
-        7 => print_style_result!(day_07, load_file("day07_input.txt"), "Day 7"),
-        8 => print_style_result!(day_08, load_file("day08_input.txt"), "Day 8"),
-        10 => print_style_result!(day_10, load_file("day10_input.txt"), "Day 10"),
-        11 => print_style_result!(day_11, load_file("day11_input.txt"), "Day 11"),
-        12 => print_style_result!(day_12, load_file("day12_input.txt"), "Day 12"),
-        13 => print_style_result!(day_13, load_file("day13_input.txt"), "Day 13"),
-        14 => print_style_result!(day_14, load_file("day14_input.txt"), "Day 14"),
-        15 => print_style_result!(day_15, load_file("day15_input.txt"), "Day 15"),
-        16 => print_style_result!(day_16, load_file("day16_input.txt"), "Day 16"),
-        _ => {}
+        1 => match day_01::parse(input) {
+            Ok(reconciler) => println!("{}", day_01::explain(&reconciler)),
+            Err(message) => println!("{}", message)
+        },
+        2 => println!("{}", day_02::explain(&day_02::parse(input))),
+        3 => println!("{}", day_03::explain(&day_03::parse(input))),
+        4 => println!("{}", day_04::explain(&day_04::parse(input))),
+        5 => println!("{}", day_05::explain(&day_05::parse(input))),
+        8 => println!("{}", day_08::explain(&day_08::parse(input))),
+        _ => println!("Day {} has no explanation", day)
     }
 }
 
+fn calibrate_equations(operators: &str, witnesses: bool, count: bool) {
+    let equations = load_file(7).map(day_07::parse);
+    let (equations, operators) = match equations.and_then(|equations| Ok((equations, day_07::parse_operators(operators)?))) {
+        Ok(loaded) => loaded,
+        Err(message) => {
+            println!("{}", message);
+            return;
+        }
+    };
+
+    for equation in &equations {
+        if witnesses {
+            day_07::witnesses(equation, &operators).iter().for_each(|witness| println!("{}", witness));
+        } else if count {
+            println!("{} has {} solution(s)", equation, day_07::count_witnesses(equation, &operators));
+        }
+    }
+    println!("{}", day_07::calibrate(&equations, &operators));
+}
 
-fn print_result<T: Display>(func: impl FnOnce(String) -> T, input: String) {
-    let timer = Instant::now();
-    let result = func(input);
-    let time = timer.elapsed();
+fn parse_cell(input: &str) -> Result<(i32, i32), String> {
+    let (column, row) = input.split_once(',').ok_or("expected column,row")?;
+    let column = column.trim().parse().map_err(|_| format!("invalid column {}", column))?;
+    let row = row.trim().parse().map_err(|_| format!("invalid row {}", row))?;
+
+    Ok((column, row))
+}
+
+fn replay_guard(delay: u64, from: usize, step: bool, obstacle: Option<(i32, i32)>, guard: usize, turn: day_06::TurnPolicy) {
+    let replay = match load_file(6).and_then(|input| day_06::replay(&day_06::parse(input), guard, obstacle, turn)) {
+        Ok(replay) => replay,
+        Err(message) => {
+            println!("{}", message);
+            return;
+        }
+    };
+
+    let last = replay.len() - 1;
+    let mut current = from.min(last);
+    while current < replay.len() {
+        println!("{}{}", ANSI_CLEAR, replay.frame(current));
+        current += 1;
+
+        if !step {
+            std::thread::sleep(Duration::from_millis(delay));
+            continue;
+        }
+        // Keep reading until the command moves the replay on, so a bad step number leaves the frame on screen.
+        loop {
+            let mut command = String::new();
+            if std::io::stdin().read_line(&mut command).unwrap_or(0) == 0 {
+                return;
+            }
+            match command.trim() {
+                "q" => return,
+                "" => break,
+                jump => match jump.parse::<usize>() {
+                    Ok(target) if target > last => println!("step {} is past the end (last step {})", target, last),
+                    Ok(target) => {
+                        current = target;
+                        break;
+                    }
+                    Err(_) => println!("Enter to step, a step number to jump, q to quit")
+                }
+            }
+        }
+    }
+}
+
+fn print_result<T: Display>(result: T, time: Duration) {
     println!(
         "{} {}(elapsed: {:.2?}){}",
         result, ANSI_ITALIC, time, ANSI_RESET
     );
 }
 
-fn load_file(path: &str) -> String {
-    let file_path = Path::new("puzzle_inputs").join(path);
+fn print_elapsed(time: Duration) {
+    println!("{}(elapsed: {:.2?}){}", ANSI_ITALIC, time, ANSI_RESET);
+}
 
-    if !file_path.exists() {
-        panic!("failure");
-    }
+fn load_file(day: i32) -> Result<String, String> {
+    let file_path = Path::new("puzzle_inputs").join(format!("day{:02}_input.txt", day));
 
-    return fs::read_to_string(file_path).unwrap();
-}
\ No newline at end of file
+    input::load_input(&file_path, day)
+}
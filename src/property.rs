@@ -0,0 +1,35 @@
+use std::env;
+use std::fmt::{Debug, Display};
+use crate::tools::Rng;
+
+const DEFAULT_SEED: u64 = 2024;
+
+// Runs a property against generated inputs. Each case gets its own seed so a failure can be replayed
+// with PROPERTY_SEED, and PROPERTY_CASES overrides how many cases run.
+pub fn check<T: Display>(cases: usize, generate: impl Fn(&mut Rng) -> T, property: impl Fn(&T) -> Result<(), String>) {
+    let base_seed = env::var("PROPERTY_SEED").ok().and_then(|s| s.parse().ok());
+    let cases = env::var("PROPERTY_CASES").ok().and_then(|s| s.parse().ok()).unwrap_or(cases);
+
+    let seeds: Vec<u64> = match base_seed {
+        Some(seed) => vec![seed],
+        None => {
+            let mut seeder = Rng::new(DEFAULT_SEED);
+            (0..cases).map(|_| seeder.next_u64()).collect()
+        }
+    };
+
+    for seed in seeds {
+        let input = generate(&mut Rng::new(seed));
+        if let Err(message) = property(&input) {
+            panic!("property failed with PROPERTY_SEED={}: {}\n{}", seed, message, input);
+        }
+    }
+}
+
+pub fn assert_same<T: PartialEq + Debug>(label: &str, expected: T, actual: T) -> Result<(), String> {
+    if expected != actual {
+        return Err(format!("{}: reference gave {:?}, optimized gave {:?}", label, expected, actual));
+    }
+
+    Ok(())
+}
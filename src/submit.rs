@@ -0,0 +1,322 @@
+use std::{env, fmt, fs};
+use std::io::Write;
+use std::path::PathBuf;
+use std::process::{Command, Stdio};
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum Outcome {
+    Correct,
+    TooHigh,
+    TooLow,
+    Wrong,
+    Wait
+}
+
+impl Outcome {
+    fn parse(value: &str) -> Option<Self> {
+        match value {
+            "correct" => Some(Outcome::Correct),
+            "too_high" => Some(Outcome::TooHigh),
+            "too_low" => Some(Outcome::TooLow),
+            "wrong" => Some(Outcome::Wrong),
+            "wait" => Some(Outcome::Wait),
+            _ => None
+        }
+    }
+
+    fn key(&self) -> &'static str {
+        match self {
+            Outcome::Correct => "correct",
+            Outcome::TooHigh => "too_high",
+            Outcome::TooLow => "too_low",
+            Outcome::Wrong => "wrong",
+            Outcome::Wait => "wait"
+        }
+    }
+
+    // The puzzle site answers with a full html page, so we just look for the phrases it uses.
+    fn from_response(body: &str) -> Option<Self> {
+        if body.contains("That's the right answer") {
+            return Some(Outcome::Correct);
+        }
+        if body.contains("You gave an answer too recently") {
+            return Some(Outcome::Wait);
+        }
+        if body.contains("your answer is too high") {
+            return Some(Outcome::TooHigh);
+        }
+        if body.contains("your answer is too low") {
+            return Some(Outcome::TooLow);
+        }
+        if body.contains("That's not the right answer") {
+            return Some(Outcome::Wrong);
+        }
+
+        None
+    }
+}
+
+impl fmt::Display for Outcome {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Outcome::Correct => write!(f, "That's the right answer!"),
+            Outcome::TooHigh => write!(f, "Answer is too high"),
+            Outcome::TooLow => write!(f, "Answer is too low"),
+            Outcome::Wrong => write!(f, "That's not the right answer"),
+            Outcome::Wait => write!(f, "Answered too recently, wait before trying again")
+        }
+    }
+}
+
+pub trait AnswerClient {
+    fn post_answer(&self, day: i32, part: i32, answer: &str) -> Result<String, String>;
+}
+
+// Posts through the system curl so we don't have to pull in a whole http stack. The session cookie goes
+// to curl as a config file on stdin so it never shows up in the process list.
+pub struct CurlClient {
+    session: String
+}
+
+impl CurlClient {
+    pub fn from_env() -> Result<Self, String> {
+        match env::var("AOC_SESSION") {
+            Ok(session) => Ok(CurlClient { session }),
+            Err(_) => Err("AOC_SESSION must be set to the session cookie to submit answers".to_string())
+        }
+    }
+}
+
+impl AnswerClient for CurlClient {
+    fn post_answer(&self, day: i32, part: i32, answer: &str) -> Result<String, String> {
+        let mut child = Command::new("curl")
+            .arg("--silent")
+            .arg("--fail")
+            .arg("--config").arg("-")
+            .arg("--data-urlencode").arg(format!("level={}", part))
+            .arg("--data-urlencode").arg(format!("answer={}", answer))
+            .arg(format!("https://adventofcode.com/2024/day/{}/answer", day))
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .spawn()
+            .map_err(|e| format!("Could not run curl: {}", e))?;
+
+        let session = self.session.replace('\\', "\\\\").replace('"', "\\\"");
+        let mut stdin = child.stdin.take().ok_or("Could not open curl's stdin".to_string())?;
+        stdin.write_all(format!("cookie = \"session={}\"\n", session).as_bytes())
+            .map_err(|e| format!("Could not pass the session to curl: {}", e))?;
+        drop(stdin);
+
+        let output = child.wait_with_output().map_err(|e| format!("Could not run curl: {}", e))?;
+
+        if !output.status.success() {
+            return Err(format!("Submission request failed with {}", output.status));
+        }
+
+        Ok(String::from_utf8_lossy(&output.stdout).to_string())
+    }
+}
+
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct Submission {
+    day: i32,
+    part: i32,
+    outcome: Outcome,
+    answer: String
+}
+
+impl Submission {
+    fn parse(input_line: &str) -> Option<Self> {
+        // Answer goes last since it is the only field that could contain a comma
+        let mut fields = input_line.splitn(4, ",");
+        let day = fields.next()?.parse().ok()?;
+        let part = fields.next()?.parse().ok()?;
+        let outcome = Outcome::parse(fields.next()?)?;
+        let answer = fields.next()?.to_string();
+
+        Some(Submission { day, part, outcome, answer })
+    }
+}
+
+impl fmt::Display for Submission {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{},{},{},{}", self.day, self.part, self.outcome.key(), self.answer)
+    }
+}
+
+pub struct SubmissionHistory {
+    path: Option<PathBuf>,
+    submissions: Vec<Submission>
+}
+
+impl SubmissionHistory {
+    pub fn load(path: PathBuf) -> Self {
+        let submissions = match fs::read_to_string(&path) {
+            Ok(contents) => contents.lines().filter_map(|line| Submission::parse(line)).collect(),
+            Err(_) => Vec::new()
+        };
+
+        SubmissionHistory { path: Some(path), submissions }
+    }
+
+    #[cfg(test)]
+    fn in_memory() -> Self {
+        SubmissionHistory { path: None, submissions: Vec::new() }
+    }
+
+    fn for_part(&self, day: i32, part: i32) -> impl Iterator<Item = &Submission> {
+        self.submissions.iter().filter(move |s| s.day == day && s.part == part)
+    }
+
+    // Returns why an answer should not be sent, if we already know how it would go.
+    fn check(&self, day: i32, part: i32, answer: &str) -> Result<(), String> {
+        if let Some(correct) = self.for_part(day, part).find(|s| s.outcome == Outcome::Correct) {
+            return Err(format!("part already solved with {}", correct.answer));
+        }
+        if let Some(previous) = self.for_part(day, part).find(|s| s.answer == answer && s.outcome != Outcome::Wait) {
+            return Err(format!("{} was already tried: {}", answer, previous.outcome));
+        }
+
+        let Ok(value) = answer.parse::<i128>() else {
+            return Ok(());
+        };
+
+        let too_high = self.for_part(day, part)
+            .filter(|s| s.outcome == Outcome::TooHigh)
+            .filter_map(|s| s.answer.parse::<i128>().ok())
+            .min();
+        let too_low = self.for_part(day, part)
+            .filter(|s| s.outcome == Outcome::TooLow)
+            .filter_map(|s| s.answer.parse::<i128>().ok())
+            .max();
+
+        if let Some(bound) = too_high {
+            if value >= bound {
+                return Err(format!("{} is not below the known too high answer {}", value, bound));
+            }
+        }
+        if let Some(bound) = too_low {
+            if value <= bound {
+                return Err(format!("{} is not above the known too low answer {}", value, bound));
+            }
+        }
+
+        Ok(())
+    }
+
+    fn record(&mut self, submission: Submission) -> Result<(), String> {
+        self.submissions.push(submission);
+
+        let Some(path) = &self.path else {
+            return Ok(());
+        };
+        let contents: String = self.submissions.iter().map(|s| format!("{}\n", s)).collect();
+        fs::write(path, contents).map_err(|e| format!("Could not write submission history: {}", e))
+    }
+}
+
+// The site's verdict on a posted answer. Once an answer is posted the outcome stands even if saving it to
+// the history fails, so that failure comes back as a warning rather than an error.
+#[derive(Debug, PartialEq, Eq)]
+pub struct Submitted {
+    pub outcome: Outcome,
+    pub warning: Option<String>
+}
+
+pub fn submit_answer(client: &dyn AnswerClient, history: &mut SubmissionHistory, day: i32, part: i32, answer: &str) -> Result<Submitted, String> {
+    let answer = answer.trim();
+    if answer.is_empty() {
+        return Err("answer is empty".to_string());
+    }
+    history.check(day, part, answer)?;
+
+    let body = client.post_answer(day, part, answer)?;
+    let outcome = Outcome::from_response(&body).ok_or("could not understand the response from the puzzle site".to_string())?;
+
+    let warning = history.record(Submission { day, part, outcome, answer: answer.to_string() }).err();
+
+    Ok(Submitted { outcome, warning })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::cell::RefCell;
+    use super::{submit_answer, AnswerClient, Outcome, Submission, SubmissionHistory};
+
+    struct FakeClient {
+        response: String,
+        posted: RefCell<Vec<String>>
+    }
+
+    impl FakeClient {
+        fn new(response: &str) -> Self {
+            FakeClient { response: response.to_string(), posted: RefCell::new(Vec::new()) }
+        }
+    }
+
+    impl AnswerClient for FakeClient {
+        fn post_answer(&self, _day: i32, _part: i32, answer: &str) -> Result<String, String> {
+            self.posted.borrow_mut().push(answer.to_string());
+            Ok(self.response.clone())
+        }
+    }
+
+    #[test]
+    fn submission_records_outcome() {
+        let client = FakeClient::new("<p>That's not the right answer; your answer is too high.</p>");
+        let mut history = SubmissionHistory::in_memory();
+
+        let submitted = submit_answer(&client, &mut history, 5, 1, "6000").unwrap();
+
+        assert_eq!(submitted.outcome, Outcome::TooHigh);
+        assert_eq!(submitted.warning, None);
+        assert_eq!(history.submissions.len(), 1);
+    }
+
+    #[test]
+    fn answers_outside_known_bounds_are_not_submitted() {
+        let client = FakeClient::new("<p>That's the right answer!</p>");
+        let mut history = SubmissionHistory::in_memory();
+        history.submissions.push(Submission::parse("5,1,too_high,6000").unwrap());
+        history.submissions.push(Submission::parse("5,1,too_low,4000").unwrap());
+
+        assert!(submit_answer(&client, &mut history, 5, 1, "6500").is_err());
+        assert!(submit_answer(&client, &mut history, 5, 1, "4000").is_err());
+        assert!(client.posted.borrow().is_empty());
+
+        assert_eq!(submit_answer(&client, &mut history, 5, 1, "5000").unwrap().outcome, Outcome::Correct);
+    }
+
+    #[test]
+    fn wrong_answers_are_not_resubmitted() {
+        let client = FakeClient::new("<p>That's the right answer!</p>");
+        let mut history = SubmissionHistory::in_memory();
+        history.submissions.push(Submission::parse("3,2,wrong,abc").unwrap());
+
+        assert!(submit_answer(&client, &mut history, 3, 2, "abc").is_err());
+        assert!(client.posted.borrow().is_empty());
+    }
+
+    #[test]
+    fn answers_after_waiting_can_be_retried() {
+        let client = FakeClient::new("<p>That's the right answer!</p>");
+        let mut history = SubmissionHistory::in_memory();
+        history.submissions.push(Submission::parse("3,2,wait,48").unwrap());
+
+        assert_eq!(submit_answer(&client, &mut history, 3, 2, "48").unwrap().outcome, Outcome::Correct);
+        assert!(submit_answer(&client, &mut history, 3, 2, "49").is_err());
+    }
+
+    #[test]
+    fn outcome_survives_a_failed_history_write() {
+        let client = FakeClient::new("<p>That's the right answer!</p>");
+        let directory = std::env::temp_dir().join("aoc-submission-history-is-a-directory");
+        std::fs::create_dir_all(&directory).unwrap();
+        let mut history = SubmissionHistory::load(directory);
+
+        let submitted = submit_answer(&client, &mut history, 1, 1, "11").unwrap();
+
+        assert_eq!(submitted.outcome, Outcome::Correct);
+        assert!(submitted.warning.unwrap().starts_with("Could not write submission history"));
+    }
+}
@@ -89,4 +89,40 @@ pub fn usize_to_i64(num: usize) -> Result<i64, String> {
     let converted_num = num as i64;
 
     Ok(converted_num)
-}
\ No newline at end of file
+}
+
+// Small seeded generator (splitmix64) so generated inputs can be reproduced from a seed without pulling in rand.
+#[derive(Debug, Clone)]
+pub struct Rng {
+    state: u64
+}
+
+impl Rng {
+    pub fn new(seed: u64) -> Self {
+        Rng { state: seed }
+    }
+
+    pub fn next_u64(&mut self) -> u64 {
+        self.state = self.state.wrapping_add(0x9E3779B97F4A7C15);
+        let mut z = self.state;
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58476D1CE4E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D049BB133111EB);
+        z ^ (z >> 31)
+    }
+
+    // Value in low..high, high exclusive
+    pub fn range(&mut self, low: i64, high: i64) -> i64 {
+        if high <= low {
+            return low;
+        }
+        low + (self.next_u64() % (high - low) as u64) as i64
+    }
+
+    pub fn chance(&mut self, percent: u32) -> bool {
+        (self.next_u64() % 100) < percent as u64
+    }
+
+    pub fn choose<'a, T>(&mut self, items: &'a [T]) -> &'a T {
+        &items[self.range(0, items.len() as i64) as usize]
+    }
+}